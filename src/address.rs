@@ -0,0 +1,378 @@
+use anyhow::{bail, Context};
+use serde::de::Error;
+use serde::{Deserialize, Deserializer};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr, TcpStream, ToSocketAddrs};
+use std::str::FromStr;
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+/// Time after which the next address is tried while the earlier attempts are still pending, as recommended by RFC 8305.
+const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// All addresses of a server, in the order connections try them.
+#[derive(Clone, Debug)]
+pub struct Addresses {
+	addresses: Vec<SocketAddr>,
+	/// Local addresses the connections are bound to, the OS picks them if empty
+	sources: Arc<[Source]>,
+	/// Index of the connection, selects the source address
+	connection: usize,
+}
+
+impl Addresses {
+	/// Looks up all A and AAAA records of `host` and alternates between the address families,
+	/// starting with IPv6 if `prefer_ipv6` is set or else with the family the resolver returned first.
+	pub fn resolve(host: &str, port: u16, prefer_ipv6: bool) -> anyhow::Result<Self> {
+		let resolved = (host, port)
+			.to_socket_addrs()
+			.with_context(|| format!("Failed to resolve {}.", host))?
+			.collect::<Vec<_>>();
+		if resolved.is_empty() {
+			bail!("{} doesn't have any addresses.", host);
+		}
+		Ok(Self {
+			addresses: interleave_families(resolved, prefer_ipv6),
+			sources: Arc::new([]),
+			connection: 0,
+		})
+	}
+
+	/// Binds the connections to `sources`, each one to the next source of the right address family.
+	pub fn with_sources(self, sources: Vec<Source>) -> Self {
+		Self {
+			sources: sources.into(),
+			..self
+		}
+	}
+
+	/// Order and source address for the connection with the given index, so that the connections are spread
+	/// across the addresses of the first family and only fall back to the other ones.
+	pub fn for_connection(&self, index: usize) -> Self {
+		let first_is_ipv6 = self.addresses[0].is_ipv6();
+		let family_size = self
+			.addresses
+			.iter()
+			.filter(|address| address.is_ipv6() == first_is_ipv6)
+			.count();
+		let start = self
+			.addresses
+			.iter()
+			.enumerate()
+			.filter(|(_, address)| address.is_ipv6() == first_is_ipv6)
+			.nth(index % family_size)
+			.map_or(0, |(position, _)| position);
+
+		let mut addresses = self.addresses.clone();
+		addresses.rotate_left(start);
+		Self {
+			addresses,
+			sources: Arc::clone(&self.sources),
+			connection: index,
+		}
+	}
+
+	/// Connects to the first address that answers. Every `CONNECTION_ATTEMPT_DELAY` or whenever an attempt fails,
+	/// the next address is tried in parallel to the pending ones.
+	pub fn connect(&self) -> io::Result<TcpStream> {
+		let (sender, receiver) = mpsc::channel();
+		let mut pending = 0;
+		let mut last_error = None;
+		for &address in &self.addresses {
+			let source = match self.source_for(address) {
+				Ok(source) => source,
+				Err(error) => {
+					last_error = Some(error);
+					continue;
+				}
+			};
+			let sender = sender.clone();
+			thread::spawn(move || {
+				// The receiver is gone if another attempt was faster
+				let _ = sender.send(connect_from(source, address));
+			});
+			pending += 1;
+
+			match receiver.recv_timeout(CONNECTION_ATTEMPT_DELAY) {
+				Ok(Ok(stream)) => return Ok(stream),
+				Ok(Err(error)) => {
+					pending -= 1;
+					last_error = Some(error);
+				}
+				Err(_) => {}
+			}
+		}
+
+		for _ in 0..pending {
+			match receiver.recv() {
+				Ok(Ok(stream)) => return Ok(stream),
+				Ok(Err(error)) => last_error = Some(error),
+				Err(_) => break,
+			}
+		}
+		Err(last_error.unwrap_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No address to connect to")))
+	}
+
+	fn source_for(&self, destination: SocketAddr) -> io::Result<Option<Source>> {
+		if self.sources.is_empty() {
+			return Ok(None);
+		}
+
+		let matching = self
+			.sources
+			.iter()
+			.filter(|source| source.is_ipv6() == destination.is_ipv6())
+			.collect::<Vec<_>>();
+		if matching.is_empty() {
+			return Err(io::Error::new(
+				io::ErrorKind::AddrNotAvailable,
+				format!("No source address of the same family as {}", destination),
+			));
+		}
+		Ok(Some(*matching[self.connection % matching.len()]))
+	}
+}
+
+/// Alternates between the address families, starting with IPv6 if `prefer_ipv6` is set
+/// or else with the family of the first address.
+fn interleave_families(resolved: Vec<SocketAddr>, prefer_ipv6: bool) -> Vec<SocketAddr> {
+	let first_is_ipv6 = prefer_ipv6 || resolved.first().is_some_and(SocketAddr::is_ipv6);
+	let (mut preferred, mut others): (Vec<_>, Vec<_>) = resolved
+		.into_iter()
+		.partition(|address| address.is_ipv6() == first_is_ipv6);
+	if preferred.is_empty() {
+		std::mem::swap(&mut preferred, &mut others);
+	}
+
+	let mut addresses = Vec::with_capacity(preferred.len() + others.len());
+	let mut others = others.into_iter();
+	for address in preferred {
+		addresses.push(address);
+		addresses.extend(others.next());
+	}
+	addresses.extend(others);
+	addresses
+}
+
+fn connect_from(source: Option<Source>, destination: SocketAddr) -> io::Result<TcpStream> {
+	let socket = Socket::new(Domain::for_address(destination), Type::STREAM, Some(Protocol::TCP))?;
+	match source {
+		Some(Source::Address(address)) => socket.bind(&SocketAddr::new(address, 0).into())?,
+		Some(source @ Source::Prefix { .. }) => {
+			// The addresses of a prefix only have to be routed to this host, they aren't assigned to an interface
+			socket.set_freebind_v6(true)?;
+			socket.bind(&SocketAddr::new(source.address(), 0).into())?;
+		}
+		None => {}
+	}
+	socket.connect_timeout(&destination.into(), CONNECT_TIMEOUT)?;
+	Ok(socket.into())
+}
+
+/// Local address for outgoing connections.
+#[derive(Clone, Copy, Debug)]
+pub enum Source {
+	Address(IpAddr),
+	/// Every connection gets a random address of the prefix
+	Prefix {
+		network: Ipv6Addr,
+		length: u8,
+	},
+}
+
+impl Source {
+	fn is_ipv6(&self) -> bool {
+		match self {
+			Self::Address(address) => address.is_ipv6(),
+			Self::Prefix { .. } => true,
+		}
+	}
+
+	fn address(&self) -> IpAddr {
+		match *self {
+			Self::Address(address) => address,
+			Self::Prefix { network, length } => {
+				let host_mask = u128::MAX.checked_shr(u32::from(length)).unwrap_or(0);
+				let address = (u128::from(network) & !host_mask) | (rand::random::<u128>() & host_mask);
+				IpAddr::V6(address.into())
+			}
+		}
+	}
+}
+
+impl FromStr for Source {
+	type Err = String;
+
+	/// Parses addresses like `192.0.2.1` and IPv6 prefixes like `2001:db8::/64`.
+	fn from_str(text: &str) -> Result<Self, Self::Err> {
+		let invalid = || format!("Invalid source address '{text}'");
+		let Some((network, length)) = text.split_once('/') else {
+			return text.parse().map(Self::Address).map_err(|_| invalid());
+		};
+		let network = network
+			.parse()
+			.map_err(|_| format!("Only IPv6 prefixes are supported, got '{text}'"))?;
+		match length.parse() {
+			Ok(length) if length <= 128 => Ok(Self::Prefix { network, length }),
+			_ => Err(invalid()),
+		}
+	}
+}
+
+impl<'de> Deserialize<'de> for Source {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		String::deserialize(deserializer)?.parse().map_err(D::Error::custom)
+	}
+}
+
+impl Display for Addresses {
+	fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+		for (index, address) in self.addresses.iter().enumerate() {
+			if index > 0 {
+				write!(formatter, ", ")?;
+			}
+			write!(formatter, "{}", address)?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn addresses(list: &[&str]) -> Vec<SocketAddr> {
+		list.iter().map(|text| text.parse().unwrap()).collect()
+	}
+
+	#[test]
+	fn interleaves_families_starting_with_the_first() {
+		let resolved = addresses(&["192.0.2.1:80", "192.0.2.2:80", "[2001:db8::1]:80", "[2001:db8::2]:80"]);
+		assert_eq!(
+			interleave_families(resolved, false),
+			addresses(&["192.0.2.1:80", "[2001:db8::1]:80", "192.0.2.2:80", "[2001:db8::2]:80"]),
+		);
+	}
+
+	#[test]
+	fn interleaves_families_starting_with_ipv6_if_preferred() {
+		let resolved = addresses(&["192.0.2.1:80", "192.0.2.2:80", "[2001:db8::1]:80"]);
+		assert_eq!(
+			interleave_families(resolved, true),
+			addresses(&["[2001:db8::1]:80", "192.0.2.1:80", "192.0.2.2:80"]),
+		);
+	}
+
+	#[test]
+	fn appends_the_rest_of_the_larger_family() {
+		let resolved = addresses(&["[2001:db8::1]:80", "192.0.2.1:80", "192.0.2.2:80", "192.0.2.3:80"]);
+		assert_eq!(interleave_families(resolved.clone(), false), resolved);
+	}
+
+	#[test]
+	fn falls_back_to_ipv4_without_ipv6_addresses() {
+		let resolved = addresses(&["192.0.2.1:80", "192.0.2.2:80"]);
+		assert_eq!(interleave_families(resolved.clone(), true), resolved);
+
+		// literal addresses don't need a DNS lookup
+		let resolved = Addresses::resolve("127.0.0.1", 1234, true).unwrap();
+		assert_eq!(resolved.addresses, addresses(&["127.0.0.1:1234"]));
+	}
+
+	#[test]
+	fn connections_rotate_through_the_first_family() {
+		let all = Addresses {
+			addresses: addresses(&["[2001:db8::1]:80", "192.0.2.1:80", "[2001:db8::2]:80", "192.0.2.2:80"]),
+			sources: Arc::new([]),
+			connection: 0,
+		};
+		assert_eq!(all.for_connection(0).addresses, all.addresses);
+
+		let second = all.for_connection(1);
+		assert_eq!(second.connection, 1);
+		assert_eq!(
+			second.addresses,
+			addresses(&["[2001:db8::2]:80", "192.0.2.2:80", "[2001:db8::1]:80", "192.0.2.1:80"]),
+		);
+		// the IPv4 addresses are only fallbacks, so the third connection starts over
+		assert_eq!(all.for_connection(2).addresses, all.addresses);
+	}
+
+	#[test]
+	fn parses_sources() {
+		assert!(matches!("192.0.2.1".parse(), Ok(Source::Address(IpAddr::V4(_)))));
+		assert!(matches!("2001:db8::1".parse(), Ok(Source::Address(IpAddr::V6(_)))));
+		assert!(matches!(
+			"2001:db8::/64".parse(),
+			Ok(Source::Prefix { length: 64, network }) if network == "2001:db8::".parse::<Ipv6Addr>().unwrap()
+		));
+		assert!("2001:db8::/128".parse::<Source>().is_ok());
+		assert!("::/0".parse::<Source>().is_ok());
+
+		assert_eq!(
+			"192.0.2.0/24".parse::<Source>().unwrap_err(),
+			"Only IPv6 prefixes are supported, got '192.0.2.0/24'"
+		);
+		assert_eq!(
+			"2001:db8::/129".parse::<Source>().unwrap_err(),
+			"Invalid source address '2001:db8::/129'"
+		);
+		assert!("2001:db8::/".parse::<Source>().is_err());
+		assert!("example.com".parse::<Source>().is_err());
+	}
+
+	#[test]
+	fn prefix_addresses_stay_inside_the_prefix() {
+		let source = "2001:db8::/64".parse::<Source>().unwrap();
+		let network = u128::from("2001:db8::".parse::<Ipv6Addr>().unwrap());
+		for _ in 0..100 {
+			let IpAddr::V6(address) = source.address() else {
+				panic!("prefix produced an IPv4 address");
+			};
+			assert_eq!(u128::from(address) >> 64, network >> 64);
+		}
+
+		let single = "2001:db8::42/128".parse::<Source>().unwrap();
+		assert_eq!(single.address(), "2001:db8::42".parse::<IpAddr>().unwrap());
+		// every address is inside of /0, it just mustn't overflow the shift
+		assert!("::/0".parse::<Source>().unwrap().address().is_ipv6());
+	}
+
+	#[test]
+	fn connections_cycle_through_sources_of_the_same_family() {
+		let sources = ["192.0.2.1", "2001:db8::/64", "192.0.2.2"]
+			.iter()
+			.map(|text| text.parse().unwrap())
+			.collect::<Vec<Source>>();
+		let all = Addresses {
+			addresses: addresses(&["198.51.100.1:80"]),
+			sources: Arc::new([]),
+			connection: 0,
+		}
+		.with_sources(sources);
+		let ipv4 = "198.51.100.1:80".parse().unwrap();
+		let ipv6 = "[2001:db8:1::1]:80".parse().unwrap();
+
+		let source_addresses = (0..4)
+			.map(|index| match all.for_connection(index).source_for(ipv4) {
+				Ok(Some(Source::Address(address))) => address,
+				other => panic!("expected an IPv4 source address, got {:?}", other),
+			})
+			.collect::<Vec<_>>();
+		let expected = ["192.0.2.1", "192.0.2.2", "192.0.2.1", "192.0.2.2"];
+		assert_eq!(source_addresses, expected.map(|text| text.parse::<IpAddr>().unwrap()));
+		assert!(matches!(
+			all.for_connection(1).source_for(ipv6),
+			Ok(Some(Source::Prefix { length: 64, .. }))
+		));
+
+		let only_ipv4 = all.clone().with_sources(vec!["192.0.2.1".parse().unwrap()]);
+		let error = only_ipv4.source_for(ipv6).unwrap_err();
+		assert_eq!(error.kind(), io::ErrorKind::AddrNotAvailable);
+		assert!(matches!(all.with_sources(Vec::new()).source_for(ipv6), Ok(None)));
+	}
+}
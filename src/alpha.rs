@@ -0,0 +1,100 @@
+use crate::address::Addresses;
+use crate::canvas;
+use crate::pixel::Color;
+use crate::{Coordinate, Dimension};
+use image::{Rgba, RgbaImage};
+use serde::Deserialize;
+
+/// How semi-transparent pixels are sent. Fully transparent pixels are always skipped.
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlphaMode {
+	/// Send the alpha value along with the color and let the server blend it.
+	#[default]
+	Server,
+	/// Read the current canvas colors below semi-transparent pixels and send the blended, opaque result.
+	Composite,
+}
+
+/// Canvas colors below the semi-transparent pixels of a frame, read before this client paints over them.
+///
+/// Every pixel is only read once, reading it again would return this client's own output and make it blend
+/// with itself. They are read again when the frame moves or changes its size.
+#[derive(Default)]
+pub struct CanvasBackground {
+	position: Coordinate,
+	width: u32,
+	height: u32,
+	/// Row by row, `None` for pixels that haven't been read
+	colors: Vec<Option<Color>>,
+	/// Set once reading failed, so that it isn't retried on every frame
+	failed: bool,
+}
+
+impl CanvasBackground {
+	pub fn new(position: Coordinate, width: u32, height: u32) -> Self {
+		Self {
+			position,
+			width,
+			height,
+			colors: vec![None; width as usize * height as usize],
+			failed: false,
+		}
+	}
+
+	/// Whether this is the background of a frame of that size at `position`.
+	pub fn covers(&self, position: Coordinate, frame: &RgbaImage) -> bool {
+		(self.position.x, self.position.y, self.width, self.height)
+			== (position.x, position.y, frame.width(), frame.height())
+	}
+
+	/// Reads the visible pixels below the semi-transparent pixels of `frame` that haven't been read yet.
+	pub fn update(
+		&mut self,
+		addresses: &Addresses,
+		frame: &RgbaImage,
+		canvas_size: Option<Dimension>,
+	) -> anyhow::Result<()> {
+		if self.failed {
+			return Ok(());
+		}
+
+		let (indices, coordinates): (Vec<_>, Vec<_>) = frame
+			.enumerate_pixels()
+			.zip(&self.colors)
+			.enumerate()
+			.filter(|(_, ((_, _, pixel), color))| matches!(pixel[3], 1..=254) && color.is_none())
+			.map(|(index, ((x, y, _), _))| (index, Coordinate::new(x, y) + self.position))
+			.filter(|&(_, coordinate)| canvas::is_visible(coordinate, canvas_size))
+			.unzip();
+		let colors = canvas::read_pixels(addresses, &coordinates).inspect_err(|_| self.failed = true)?;
+		for (index, color) in indices.into_iter().zip(colors) {
+			self.colors[index] = Some(color);
+		}
+		Ok(())
+	}
+
+	/// Blends all semi-transparent pixels of `frame` with the background that has been read.
+	pub fn composite(&self, frame: &RgbaImage) -> RgbaImage {
+		let mut composited = frame.clone();
+		for (pixel, background) in composited.pixels_mut().zip(&self.colors) {
+			if let (Some(background), 1..=254) = (background, pixel[3]) {
+				*pixel = blend(Color::from(*pixel), *background);
+			}
+		}
+		composited
+	}
+}
+
+fn blend(foreground: Color, background: Color) -> Rgba<u8> {
+	let alpha = u32::from(foreground.alpha());
+	let mix = |foreground: u8, background: u8| {
+		((u32::from(foreground) * alpha + u32::from(background) * (255 - alpha) + 127) / 255) as u8
+	};
+	Rgba([
+		mix(foreground.red(), background.red()),
+		mix(foreground.green(), background.green()),
+		mix(foreground.blue(), background.blue()),
+		u8::MAX,
+	])
+}
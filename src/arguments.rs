@@ -0,0 +1,38 @@
+use anyhow::{bail, Context};
+
+/// What the binary should do, selected via command line arguments.
+pub enum Mode {
+	/// Paint the style configured in `config.toml`
+	Config,
+	/// `--benchmark-encoder`
+	BenchmarkEncoder,
+	/// `--stdin-raw WxH`: paint raw RGBA frames of the given size read from stdin
+	StdinRaw { width: u32, height: u32 },
+}
+
+impl Mode {
+	pub fn from_arguments(mut arguments: impl Iterator<Item = String>) -> anyhow::Result<Self> {
+		let mode = match arguments.next().as_deref() {
+			None => Mode::Config,
+			Some("--benchmark-encoder") => Mode::BenchmarkEncoder,
+			Some("--stdin-raw") => {
+				let size = arguments
+					.next()
+					.context("--stdin-raw requires a frame size like 640x480")?;
+				let (width, height) = size
+					.split_once('x')
+					.context("Frame size for --stdin-raw must look like 640x480")?;
+				Mode::StdinRaw {
+					width: width.parse().context("Invalid frame width")?,
+					height: height.parse().context("Invalid frame height")?,
+				}
+			}
+			Some(argument) => bail!("Unknown argument '{argument}'"),
+		};
+
+		if let Some(argument) = arguments.next() {
+			bail!("Unexpected argument '{argument}'");
+		}
+		Ok(mode)
+	}
+}
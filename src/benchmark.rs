@@ -0,0 +1,47 @@
+use crate::frame_serializer::{FrameSerializer, RowSerializer};
+use crate::pixel_encoder::PixelEncoder;
+use crate::Pixel;
+use image::{Rgba, RgbaImage};
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+const FRAME_WIDTH: u32 = 1920;
+const FRAME_HEIGHT: u32 = 1080;
+const MEASUREMENT_DURATION: Duration = Duration::from_secs(3);
+
+/// Measures how many pixels per second the `PixelEncoder` and the `Display` implementation of `Pixel` can encode
+/// on a single thread.
+pub fn run_encoder_benchmark() {
+	let mut rng = SmallRng::from_entropy();
+	let frame = RgbaImage::from_fn(FRAME_WIDTH, FRAME_HEIGHT, |_, _| {
+		Rgba([rng.gen(), rng.gen(), rng.gen(), u8::MAX])
+	});
+	let pixels = RowSerializer.serialize(&frame).collect::<Vec<_>>();
+
+	let encoder = PixelEncoder::default();
+	let table_rate = measure(&pixels, |pixel, buffer| encoder.encode(pixel, buffer));
+	println!("Lookup table encoder: {:.0} pixels/s", table_rate);
+
+	let format_rate = measure(&pixels, |pixel, buffer| {
+		let _ = buffer.write_fmt(format_args!("{pixel}"));
+	});
+	println!("Display formatting:   {:.0} pixels/s", format_rate);
+	println!("Speedup: {:.2}x", table_rate / format_rate);
+}
+
+fn measure(pixels: &[Pixel], mut encode: impl FnMut(Pixel, &mut Vec<u8>)) -> f64 {
+	let mut buffer = Vec::with_capacity(pixels.len() * Pixel::BYTE_ESTIMATE);
+	let mut encoded_pixels = 0;
+	let start = Instant::now();
+	while start.elapsed() < MEASUREMENT_DURATION {
+		buffer.clear();
+		for pixel in pixels {
+			encode(*pixel, &mut buffer);
+		}
+		encoded_pixels += pixels.len();
+	}
+
+	encoded_pixels as f64 / start.elapsed().as_secs_f64()
+}
@@ -0,0 +1,149 @@
+use crate::{Coordinate, Dimension, Pixel};
+use serde::Deserialize;
+use std::ops::Range;
+
+/// Part of the frame with its own share of the connections.
+#[derive(Clone, Debug)]
+pub struct Region {
+	/// Position inside the painted frame
+	pub offset: Coordinate,
+	pub dimension: Dimension,
+	pub weight: f64,
+}
+
+impl Region {
+	fn contains(&self, coordinate: Coordinate) -> bool {
+		let relative = Coordinate {
+			x: coordinate.x - self.offset.x,
+			y: coordinate.y - self.offset.y,
+		};
+		self.dimension.contains(relative)
+	}
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetMode {
+	/// Every region gets at least one connection, the others are handed out in proportion to the weights.
+	#[default]
+	Proportional,
+	/// The regions with the highest weight share all connections, except for one per other region
+	/// so that those still get painted.
+	Priority,
+}
+
+/// How the connections of a painter are shared between regions of the frame.
+///
+/// Pixels belong to the last region containing them, the ones outside of all regions form an implicit region
+/// with a weight of 1.0. Without regions all connections share the whole frame evenly.
+#[derive(Clone, Debug, Default)]
+pub struct Budget {
+	pub mode: BudgetMode,
+	pub regions: Vec<Region>,
+}
+
+impl Budget {
+	/// Sorts `pixels` by region and returns the ranges of `pixels` every stream has to paint, in order.
+	pub fn split(&self, pixels: &mut Vec<Pixel>, stream_count: usize) -> Vec<Vec<Range<usize>>> {
+		let mut groups = vec![Vec::new(); self.regions.len() + 1];
+		for pixel in pixels.drain(..) {
+			let region = self
+				.regions
+				.iter()
+				.rposition(|region| region.contains(pixel.coordinate))
+				.unwrap_or(self.regions.len());
+			groups[region].push(pixel);
+		}
+
+		// (range of pixels, weight) of every region that has something to paint
+		let mut ranges = Vec::new();
+		for (index, group) in groups.into_iter().enumerate() {
+			if group.is_empty() {
+				continue;
+			}
+			let start = pixels.len();
+			pixels.extend(group);
+			let weight = self.regions.get(index).map_or(1.0, |region| region.weight.max(0.0));
+			ranges.push((start..pixels.len(), weight));
+		}
+
+		if ranges.is_empty() {
+			return vec![Vec::new(); stream_count];
+		}
+		match self.mode {
+			BudgetMode::Proportional => split_proportional(&ranges, stream_count),
+			BudgetMode::Priority => {
+				// Stable, so regions of the same weight stay in order
+				ranges.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+				let highest = ranges[0].1;
+				let priorities = ranges
+					.into_iter()
+					.map(|(range, weight)| (range, if weight == highest { 1.0 } else { 0.0 }))
+					.collect::<Vec<_>>();
+				split_proportional(&priorities, stream_count)
+			}
+		}
+	}
+}
+
+fn split_proportional(ranges: &[(Range<usize>, f64)], stream_count: usize) -> Vec<Vec<Range<usize>>> {
+	// Not enough streams for every region, neighbouring regions have to share one
+	if stream_count < ranges.len() {
+		return (0..stream_count)
+			.map(|stream| {
+				let first = stream * ranges.len() / stream_count;
+				let last = (stream + 1) * ranges.len() / stream_count;
+				ranges[first..last].iter().map(|(range, _)| range.clone()).collect()
+			})
+			.collect();
+	}
+
+	let counts = stream_counts(ranges.iter().map(|(_, weight)| *weight), stream_count);
+	ranges
+		.iter()
+		.zip(counts)
+		.flat_map(|((range, _), count)| divide(range.clone(), count))
+		.map(|range| vec![range])
+		.collect()
+}
+
+/// One stream for every weight and the remaining ones distributed by the largest remainder method.
+fn stream_counts(weights: impl Iterator<Item = f64> + Clone, stream_count: usize) -> Vec<usize> {
+	let region_count = weights.clone().count();
+	let remaining = stream_count - region_count;
+	let total = weights.clone().sum::<f64>();
+	let shares = weights
+		.map(|weight| {
+			if total > 0.0 {
+				remaining as f64 * weight / total
+			} else {
+				remaining as f64 / region_count as f64
+			}
+		})
+		.collect::<Vec<_>>();
+
+	let mut counts = shares
+		.iter()
+		.map(|share| 1 + share.floor() as usize)
+		.collect::<Vec<_>>();
+	let mut by_remainder = (0..region_count).collect::<Vec<_>>();
+	by_remainder.sort_by(|&a, &b| (shares[b] - shares[b].floor()).total_cmp(&(shares[a] - shares[a].floor())));
+	let assigned = counts.iter().sum::<usize>();
+	for &index in by_remainder.iter().cycle().take(stream_count - assigned) {
+		counts[index] += 1;
+	}
+	counts
+}
+
+/// Splits the non-empty `range` into `count` consecutive parts of about the same size.
+///
+/// With fewer elements than parts, neighbouring parts share an element,
+/// so that no stream is left with nothing to paint.
+fn divide(range: Range<usize>, count: usize) -> impl Iterator<Item = Range<usize>> {
+	let length = range.len();
+	(0..count).map(move |index| {
+		let start = index * length / count;
+		let end = ((index + 1) * length / count).max(start + 1);
+		range.start + start..range.start + end
+	})
+}
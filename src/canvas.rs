@@ -0,0 +1,98 @@
+use crate::address::Addresses;
+use crate::pixel::Color;
+use crate::{Coordinate, Dimension};
+use anyhow::{anyhow, bail, Context};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::thread;
+use std::time::Duration;
+
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Asks the server for the size of its canvas via `SIZE`.
+pub fn read_size(addresses: &Addresses) -> anyhow::Result<Dimension> {
+	let mut stream = addresses
+		.connect()
+		.context("Failed to connect for reading the canvas size")?;
+	stream.set_read_timeout(Some(READ_TIMEOUT))?;
+	stream.write_all(b"SIZE\n")?;
+
+	let mut line = String::new();
+	BufReader::new(stream).read_line(&mut line)?;
+	let mut parts = line.split_whitespace();
+	let (Some("SIZE"), Some(width), Some(height)) = (parts.next(), parts.next(), parts.next()) else {
+		bail!("Invalid response to size request: {}", line.trim_end());
+	};
+	Ok(Dimension {
+		width: width.parse().context("Invalid canvas width")?,
+		height: height.parse().context("Invalid canvas height")?,
+	})
+}
+
+/// Whether a pixel at `coordinate` can be drawn, `canvas_size` is `None` if the server didn't tell it.
+pub fn is_visible(coordinate: Coordinate, canvas_size: Option<Dimension>) -> bool {
+	match canvas_size {
+		Some(canvas_size) => canvas_size.contains(coordinate),
+		None => coordinate.x >= 0 && coordinate.y >= 0,
+	}
+}
+
+/// Reads the current colors of the given canvas coordinates, in the same order.
+///
+/// All `PX x y` requests are pipelined over a single connection while the responses are read back.
+pub fn read_pixels(addresses: &Addresses, coordinates: &[Coordinate]) -> anyhow::Result<Vec<Color>> {
+	if coordinates.is_empty() {
+		return Ok(Vec::new());
+	}
+
+	let stream = addresses
+		.connect()
+		.context("Failed to connect for reading the canvas")?;
+	stream.set_read_timeout(Some(READ_TIMEOUT))?;
+
+	let request_stream = stream.try_clone()?;
+	let requests = coordinates.to_vec();
+	let writer = thread::spawn(move || -> std::io::Result<()> {
+		let mut writer = BufWriter::new(request_stream);
+		for Coordinate { x, y } in requests {
+			writeln!(writer, "PX {x} {y}")?;
+		}
+		writer.flush()
+	});
+
+	let mut reader = BufReader::new(stream);
+	let mut line = String::new();
+	let mut colors = Vec::with_capacity(coordinates.len());
+	for coordinate in coordinates {
+		line.clear();
+		if reader.read_line(&mut line)? == 0 {
+			bail!("Connection closed while reading the canvas");
+		}
+		colors.push(parse_pixel_response(&line, *coordinate)?);
+	}
+
+	writer.join().map_err(|_| anyhow!("Canvas request writer panicked"))??;
+	Ok(colors)
+}
+
+/// Parses a `PX x y rrggbb` response.
+fn parse_pixel_response(line: &str, coordinate: Coordinate) -> anyhow::Result<Color> {
+	let mut parts = line.split_whitespace();
+	let (Some("PX"), Some(x), Some(y), Some(color)) = (parts.next(), parts.next(), parts.next(), parts.next()) else {
+		bail!("Invalid response to pixel request: {}", line.trim_end());
+	};
+	if (x.parse(), y.parse()) != (Ok(coordinate.x), Ok(coordinate.y)) {
+		bail!("Response for {x} {y} doesn't match request for {coordinate}");
+	}
+
+	let value = u32::from_str_radix(color, 16).with_context(|| format!("Invalid color in response: {color}"))?;
+	match color.len() {
+		6 => Ok(Color::rgb((value >> 16) as u8, (value >> 8) as u8, value as u8)),
+		8 => Ok(Color::rgba(
+			(value >> 24) as u8,
+			(value >> 16) as u8,
+			(value >> 8) as u8,
+			value as u8,
+		)),
+		_ => bail!("Invalid color in response: {color}"),
+	}
+}
@@ -1,21 +1,219 @@
-use serde::Deserialize;
-use std::f64;
+use crate::double_double::DoubleDouble;
+use serde::{Deserialize, Deserializer};
 use std::ops::Add;
+use std::ops::AddAssign;
 use std::ops::Div;
+use std::ops::DivAssign;
 use std::ops::Mul;
+use std::ops::MulAssign;
 use std::ops::Neg;
 use std::ops::Sub;
+use std::ops::SubAssign;
+use std::str::FromStr;
 
-#[derive(Copy, Clone, Debug, Deserialize)]
-pub struct Complex {
-	pub real: f64,
-	pub imag: f64,
+/// Complex number, `f64` unless more precision is needed for deep zooms.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Complex<T = f64> {
+	pub real: T,
+	pub imag: T,
 }
 
-impl Add for Complex {
-	type Output = Complex;
+/// Floating point types the transcendental functions of `Complex` are available for.
+pub trait Float:
+	Copy
+	+ PartialOrd
+	+ Add<Output = Self>
+	+ Sub<Output = Self>
+	+ Mul<Output = Self>
+	+ Div<Output = Self>
+	+ Neg<Output = Self>
+{
+	const ZERO: Self;
+	const ONE: Self;
 
-	fn add(self, other: Complex) -> Complex {
+	fn hypot(self, other: Self) -> Self;
+	fn atan2(self, other: Self) -> Self;
+	fn exp(self) -> Self;
+	fn ln(self) -> Self;
+	fn powf(self, exponent: Self) -> Self;
+	fn sin(self) -> Self;
+	fn cos(self) -> Self;
+	fn sinh(self) -> Self;
+	fn cosh(self) -> Self;
+}
+
+macro_rules! impl_float {
+	($type:ident) => {
+		impl Float for $type {
+			const ZERO: Self = 0.0;
+			const ONE: Self = 1.0;
+
+			fn hypot(self, other: Self) -> Self {
+				$type::hypot(self, other)
+			}
+
+			fn atan2(self, other: Self) -> Self {
+				$type::atan2(self, other)
+			}
+
+			fn exp(self) -> Self {
+				$type::exp(self)
+			}
+
+			fn ln(self) -> Self {
+				$type::ln(self)
+			}
+
+			fn powf(self, exponent: Self) -> Self {
+				$type::powf(self, exponent)
+			}
+
+			fn sin(self) -> Self {
+				$type::sin(self)
+			}
+
+			fn cos(self) -> Self {
+				$type::cos(self)
+			}
+
+			fn sinh(self) -> Self {
+				$type::sinh(self)
+			}
+
+			fn cosh(self) -> Self {
+				$type::cosh(self)
+			}
+		}
+
+		impl Add<Complex<$type>> for $type {
+			type Output = Complex<$type>;
+
+			fn add(self, other: Complex<$type>) -> Complex<$type> {
+				other + self
+			}
+		}
+
+		impl Sub<Complex<$type>> for $type {
+			type Output = Complex<$type>;
+
+			fn sub(self, other: Complex<$type>) -> Complex<$type> {
+				-other + self
+			}
+		}
+
+		impl Mul<Complex<$type>> for $type {
+			type Output = Complex<$type>;
+
+			fn mul(self, other: Complex<$type>) -> Complex<$type> {
+				other * self
+			}
+		}
+
+		impl Div<Complex<$type>> for $type {
+			type Output = Complex<$type>;
+
+			fn div(self, other: Complex<$type>) -> Complex<$type> {
+				Complex::from((self, 0.0)) / other
+			}
+		}
+	};
+}
+
+impl_float!(f32);
+impl_float!(f64);
+
+impl<T> Complex<T> {
+	pub fn new(real: T, imag: T) -> Self {
+		Self { real, imag }
+	}
+}
+
+impl<T: Copy + Add<Output = T> + Mul<Output = T>> Complex<T> {
+	/// Square of `abs`, cheaper because it doesn't need the square root.
+	pub fn norm_sqr(self) -> T {
+		self.real * self.real + self.imag * self.imag
+	}
+}
+
+impl<T: Neg<Output = T>> Complex<T> {
+	pub fn conj(self) -> Self {
+		Self {
+			real: self.real,
+			imag: -self.imag,
+		}
+	}
+}
+
+impl<T: Float> Complex<T> {
+	pub fn abs(self) -> T {
+		self.real.hypot(self.imag)
+	}
+
+	/// Angle to the positive real axis in `-π..=π`
+	pub fn arg(self) -> T {
+		self.imag.atan2(self.real)
+	}
+
+	fn from_polar(radius: T, angle: T) -> Self {
+		Self {
+			real: radius * angle.cos(),
+			imag: radius * angle.sin(),
+		}
+	}
+
+	pub fn exp(self) -> Self {
+		Self::from_polar(self.real.exp(), self.imag)
+	}
+
+	/// Principal value of the natural logarithm
+	pub fn ln(self) -> Self {
+		Self {
+			real: self.abs().ln(),
+			imag: self.arg(),
+		}
+	}
+
+	pub fn powf(self, exponent: T) -> Self {
+		if self.real == T::ZERO && self.imag == T::ZERO {
+			return if exponent == T::ZERO {
+				Self::new(T::ONE, T::ZERO)
+			} else {
+				self
+			};
+		}
+		Self::from_polar(self.abs().powf(exponent), self.arg() * exponent)
+	}
+
+	pub fn powc(self, exponent: Self) -> Self {
+		if self.real == T::ZERO && self.imag == T::ZERO {
+			return if exponent.real == T::ZERO && exponent.imag == T::ZERO {
+				Self::new(T::ONE, T::ZERO)
+			} else {
+				self
+			};
+		}
+		(self.ln() * exponent).exp()
+	}
+
+	pub fn sin(self) -> Self {
+		Self {
+			real: self.real.sin() * self.imag.cosh(),
+			imag: self.real.cos() * self.imag.sinh(),
+		}
+	}
+
+	pub fn cos(self) -> Self {
+		Self {
+			real: self.real.cos() * self.imag.cosh(),
+			imag: -(self.real.sin() * self.imag.sinh()),
+		}
+	}
+}
+
+impl<T: Add<Output = T>> Add for Complex<T> {
+	type Output = Complex<T>;
+
+	fn add(self, other: Complex<T>) -> Complex<T> {
 		Complex {
 			real: self.real + other.real,
 			imag: self.imag + other.imag,
@@ -23,10 +221,10 @@ impl Add for Complex {
 	}
 }
 
-impl Mul for Complex {
-	type Output = Complex;
+impl<T: Copy + Add<Output = T> + Sub<Output = T> + Mul<Output = T>> Mul for Complex<T> {
+	type Output = Complex<T>;
 
-	fn mul(self, other: Complex) -> Complex {
+	fn mul(self, other: Complex<T>) -> Complex<T> {
 		Complex {
 			real: self.real * other.real - self.imag * other.imag,
 			imag: self.real * other.imag + self.imag * other.real,
@@ -34,10 +232,10 @@ impl Mul for Complex {
 	}
 }
 
-impl Sub for Complex {
-	type Output = Complex;
+impl<T: Sub<Output = T>> Sub for Complex<T> {
+	type Output = Complex<T>;
 
-	fn sub(self, other: Complex) -> Complex {
+	fn sub(self, other: Complex<T>) -> Complex<T> {
 		Complex {
 			real: self.real - other.real,
 			imag: self.imag - other.imag,
@@ -45,10 +243,10 @@ impl Sub for Complex {
 	}
 }
 
-impl Neg for Complex {
-	type Output = Complex;
+impl<T: Neg<Output = T>> Neg for Complex<T> {
+	type Output = Complex<T>;
 
-	fn neg(self) -> Complex {
+	fn neg(self) -> Complex<T> {
 		Complex {
 			real: -self.real,
 			imag: -self.imag,
@@ -56,10 +254,10 @@ impl Neg for Complex {
 	}
 }
 
-impl Div for Complex {
-	type Output = Complex;
+impl<T: Copy + Add<Output = T> + Sub<Output = T> + Mul<Output = T> + Div<Output = T>> Div for Complex<T> {
+	type Output = Complex<T>;
 
-	fn div(self, other: Complex) -> Complex {
+	fn div(self, other: Complex<T>) -> Complex<T> {
 		Complex {
 			real: ((self.real * other.real) + (self.imag * other.imag))
 				/ ((other.real * other.real) + (other.imag * other.imag)),
@@ -69,6 +267,304 @@ impl Div for Complex {
 	}
 }
 
-pub fn abs(number: Complex) -> f64 {
-	f64::sqrt(number.real * number.real + number.imag * number.imag)
+impl<T: Add<Output = T>> Add<T> for Complex<T> {
+	type Output = Complex<T>;
+
+	fn add(self, other: T) -> Complex<T> {
+		Complex {
+			real: self.real + other,
+			imag: self.imag,
+		}
+	}
+}
+
+impl<T: Sub<Output = T>> Sub<T> for Complex<T> {
+	type Output = Complex<T>;
+
+	fn sub(self, other: T) -> Complex<T> {
+		Complex {
+			real: self.real - other,
+			imag: self.imag,
+		}
+	}
+}
+
+impl<T: Copy + Mul<Output = T>> Mul<T> for Complex<T> {
+	type Output = Complex<T>;
+
+	fn mul(self, other: T) -> Complex<T> {
+		Complex {
+			real: self.real * other,
+			imag: self.imag * other,
+		}
+	}
+}
+
+impl<T: Copy + Div<Output = T>> Div<T> for Complex<T> {
+	type Output = Complex<T>;
+
+	fn div(self, other: T) -> Complex<T> {
+		Complex {
+			real: self.real / other,
+			imag: self.imag / other,
+		}
+	}
+}
+
+macro_rules! impl_assign {
+	($assign_trait:ident, $assign_method:ident, $trait:ident, $method:ident) => {
+		impl<T: Copy> $assign_trait for Complex<T>
+		where
+			Complex<T>: $trait<Output = Complex<T>>,
+		{
+			fn $assign_method(&mut self, other: Complex<T>) {
+				*self = (*self).$method(other);
+			}
+		}
+
+		impl<T: Copy> $assign_trait<T> for Complex<T>
+		where
+			Complex<T>: $trait<T, Output = Complex<T>>,
+		{
+			fn $assign_method(&mut self, other: T) {
+				*self = (*self).$method(other);
+			}
+		}
+	};
+}
+
+impl_assign!(AddAssign, add_assign, Add, add);
+impl_assign!(SubAssign, sub_assign, Sub, sub);
+impl_assign!(MulAssign, mul_assign, Mul, mul);
+impl_assign!(DivAssign, div_assign, Div, div);
+
+impl<T> From<(T, T)> for Complex<T> {
+	fn from((real, imag): (T, T)) -> Self {
+		Self { real, imag }
+	}
+}
+
+impl From<Complex<DoubleDouble>> for Complex {
+	fn from(number: Complex<DoubleDouble>) -> Self {
+		Complex {
+			real: number.real.into(),
+			imag: number.imag.into(),
+		}
+	}
+}
+
+impl From<Complex> for Complex<DoubleDouble> {
+	fn from(number: Complex) -> Self {
+		Complex {
+			real: number.real.into(),
+			imag: number.imag.into(),
+		}
+	}
+}
+
+impl<T: FromStr + Neg<Output = T>> FromStr for Complex<T> {
+	type Err = String;
+
+	/// Parses numbers like `-0.8+0.156i`, `2.5`, `-i` or `1e-3-2e-2i`.
+	fn from_str(text: &str) -> Result<Self, Self::Err> {
+		let invalid = || format!("Invalid complex number '{text}', expected something like -0.8+0.156i");
+		let parse = |number: &str| number.parse::<T>().map_err(|_| invalid());
+		let text = text
+			.chars()
+			.filter(|character| !character.is_whitespace())
+			.collect::<String>();
+		let Some(without_i) = text.strip_suffix('i') else {
+			return Ok(Self {
+				real: parse(&text)?,
+				imag: parse("0")?,
+			});
+		};
+
+		// the sign between both parts, signs at the start or of exponents don't count
+		let split = without_i
+			.char_indices()
+			.rev()
+			.find(|(index, character)| {
+				*index > 0 && matches!(character, '+' | '-') && !without_i[..*index].ends_with(['e', 'E'])
+			})
+			.map(|(index, _)| index);
+		let (real, imag) = match split {
+			Some(index) => (parse(&without_i[..index])?, &without_i[index..]),
+			None => (parse("0")?, without_i),
+		};
+		let imag = match imag {
+			"" | "+" => parse("1")?,
+			"-" => -parse("1")?,
+			imag => parse(imag.strip_prefix('+').unwrap_or(imag))?,
+		};
+		Ok(Self { real, imag })
+	}
+}
+
+impl<'de, T: Deserialize<'de> + FromStr + Neg<Output = T>> Deserialize<'de> for Complex<T> {
+	/// Accepts either `{ real = -0.8, imag = 0.156 }` or `"-0.8+0.156i"`.
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		#[derive(Deserialize)]
+		#[serde(untagged)]
+		enum Representation<T> {
+			Text(String),
+			Parts { real: T, imag: T },
+		}
+
+		match Representation::deserialize(deserializer)? {
+			Representation::Text(text) => text.parse().map_err(serde::de::Error::custom),
+			Representation::Parts { real, imag } => Ok(Self { real, imag }),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::f64::consts::{E, FRAC_PI_2, PI};
+
+	fn assert_close(actual: Complex, expected: (f64, f64)) {
+		let expected = Complex::from(expected);
+		assert!(
+			(actual - expected).abs() < 1e-9,
+			"expected {:?}, got {:?}",
+			expected,
+			actual
+		);
+	}
+
+	#[test]
+	fn arithmetic() {
+		let a = Complex::new(1.0, 2.0);
+		let b = Complex::new(3.0, -1.0);
+		assert_eq!(a + b, Complex::new(4.0, 1.0));
+		assert_eq!(a - b, Complex::new(-2.0, 3.0));
+		assert_eq!(a * b, Complex::new(5.0, 5.0));
+		assert_close(a / b, (0.1, 0.7));
+		assert_eq!(-a, Complex::new(-1.0, -2.0));
+	}
+
+	#[test]
+	fn scalar_arithmetic() {
+		let a = Complex::new(1.0, 2.0);
+		assert_eq!(a + 1.0, Complex::new(2.0, 2.0));
+		assert_eq!(a - 1.0, Complex::new(0.0, 2.0));
+		assert_eq!(a * 2.0, Complex::new(2.0, 4.0));
+		assert_eq!(a / 2.0, Complex::new(0.5, 1.0));
+		assert_eq!(2.0 * a, Complex::new(2.0, 4.0));
+		assert_eq!(1.0 + a, Complex::new(2.0, 2.0));
+		assert_eq!(1.0 - a, Complex::new(0.0, -2.0));
+		assert_close(1.0 / Complex::new(0.0, 1.0), (0.0, -1.0));
+	}
+
+	#[test]
+	fn assign_operators() {
+		let mut a = Complex::new(1.0, 2.0);
+		a += Complex::new(1.0, 1.0);
+		assert_eq!(a, Complex::new(2.0, 3.0));
+		a -= Complex::new(2.0, 0.0);
+		assert_eq!(a, Complex::new(0.0, 3.0));
+		a *= Complex::new(0.0, 1.0);
+		assert_eq!(a, Complex::new(-3.0, 0.0));
+		a /= Complex::new(-3.0, 0.0);
+		assert_eq!(a, Complex::new(1.0, 0.0));
+		a += 1.0;
+		a *= 3.0;
+		a -= 2.0;
+		a /= 2.0;
+		assert_eq!(a, Complex::new(2.0, 0.0));
+	}
+
+	#[test]
+	fn norm_abs_arg_conj() {
+		let a = Complex::new(3.0, -4.0);
+		assert_eq!(a.norm_sqr(), 25.0);
+		assert_eq!(a.abs(), 5.0);
+		assert_eq!(a.conj(), Complex::new(3.0, 4.0));
+		assert_eq!(Complex::new(0.0, 1.0).arg(), FRAC_PI_2);
+		assert_eq!(Complex::new(-1.0, 0.0).arg(), PI);
+	}
+
+	#[test]
+	fn exp_and_ln() {
+		assert_close(Complex::new(1.0, 0.0).exp(), (E, 0.0));
+		// Euler's identity
+		assert_close(Complex::new(0.0, PI).exp(), (-1.0, 0.0));
+		assert_close(Complex::new(-1.0, 0.0).ln(), (0.0, PI));
+		let a = Complex::new(0.3, -1.7);
+		assert_close(a.ln().exp(), (0.3, -1.7));
+	}
+
+	#[test]
+	fn powers() {
+		let a = Complex::new(1.0, 1.0);
+		assert_close(a.powf(2.0), (0.0, 2.0));
+		assert_close(a.powf(0.5) * a.powf(0.5), (1.0, 1.0));
+		assert_close(a.powc(Complex::new(2.0, 0.0)), (0.0, 2.0));
+		// i^i is real
+		assert_close(
+			Complex::new(0.0, 1.0).powc(Complex::new(0.0, 1.0)),
+			((-FRAC_PI_2).exp(), 0.0),
+		);
+		assert_eq!(Complex::new(0.0, 0.0).powf(2.0), Complex::new(0.0, 0.0));
+		assert_eq!(Complex::new(0.0, 0.0).powf(0.0), Complex::new(1.0, 0.0));
+		assert_eq!(
+			Complex::new(0.0, 0.0).powc(Complex::new(0.0, 0.0)),
+			Complex::new(1.0, 0.0)
+		);
+	}
+
+	#[test]
+	fn trigonometry() {
+		assert_close(Complex::new(FRAC_PI_2, 0.0).sin(), (1.0, 0.0));
+		assert_close(Complex::new(PI, 0.0).cos(), (-1.0, 0.0));
+		let a = Complex::new(0.7, -0.4);
+		let identity = a.sin() * a.sin() + a.cos() * a.cos();
+		assert_close(identity, (1.0, 0.0));
+	}
+
+	#[test]
+	fn single_precision() {
+		let a = Complex::<f32>::new(3.0, 4.0);
+		assert_eq!(a.abs(), 5.0);
+		assert_eq!(a * 2.0, Complex::new(6.0f32, 8.0));
+		assert!((a.ln().exp() - a).abs() < 1e-5);
+	}
+
+	#[test]
+	fn from_tuple() {
+		assert_eq!(Complex::from((1.5, -2.0)), Complex::new(1.5, -2.0));
+	}
+
+	#[test]
+	fn parse() {
+		let parse = |text: &str| text.parse::<Complex>().unwrap();
+		assert_eq!(parse("-0.8+0.156i"), Complex::new(-0.8, 0.156));
+		assert_eq!(parse("0.285 - 0.01i"), Complex::new(0.285, -0.01));
+		assert_eq!(parse("2.5"), Complex::new(2.5, 0.0));
+		assert_eq!(parse("-3i"), Complex::new(0.0, -3.0));
+		assert_eq!(parse("i"), Complex::new(0.0, 1.0));
+		assert_eq!(parse("-i"), Complex::new(0.0, -1.0));
+		assert_eq!(parse("1-i"), Complex::new(1.0, -1.0));
+		assert_eq!(parse("1e-3-2E+2i"), Complex::new(1e-3, -2e2));
+		assert!("1+2j".parse::<Complex>().is_err());
+		assert!("".parse::<Complex>().is_err());
+	}
+
+	#[test]
+	fn deserialize() {
+		#[derive(Deserialize)]
+		struct Config {
+			text: Complex,
+			parts: Complex,
+			precise: Complex<DoubleDouble>,
+		}
+
+		let config: Config =
+			toml::from_str("text = \"-0.8+0.156i\"\nparts = { real = 1.0, imag = -2.0 }\nprecise = \"-1.5+0.25i\"")
+				.unwrap();
+		assert_eq!(config.text, Complex::new(-0.8, 0.156));
+		assert_eq!(config.parts, Complex::new(1.0, -2.0));
+		assert_eq!(Complex::from(config.precise), Complex::new(-1.5, 0.25));
+	}
 }
@@ -0,0 +1,127 @@
+use crate::frame_painter::{resize_frame, ResizeType};
+use crate::frame_source::FrameSource;
+use crate::transform::over;
+use crate::{Coordinate, Dimension};
+use anyhow::Context;
+use image::imageops::FilterType;
+use image::{DynamicImage, RgbaImage};
+use std::convert::TryInto;
+use std::time::{Duration, Instant};
+
+/// Region of the composited frame that shows the frames of its own source.
+pub struct Layer {
+	pub name: String,
+	pub source: Box<dyn FrameSource>,
+	pub offset: Coordinate,
+	pub dimension: Dimension,
+	pub z: i32,
+	pub opacity: f32,
+	pub resize_type: ResizeType,
+	pub resize_filter: FilterType,
+}
+
+struct ActiveLayer {
+	layer: Layer,
+	frame: Option<RgbaImage>,
+	/// `None` once a static layer has produced its frame
+	next_update: Option<Instant>,
+}
+
+/// Flattens several layers into one frame, updated whenever one of them changes.
+pub struct Compositor {
+	layers: Vec<ActiveLayer>,
+	dimension: Dimension,
+}
+
+impl Compositor {
+	pub fn new(mut layers: Vec<Layer>, dimension: Dimension) -> Self {
+		// Stable, so equal z values keep the order of the config
+		layers.sort_by_key(|layer| layer.z);
+		let now = Instant::now();
+		let layers = layers
+			.into_iter()
+			.map(|layer| ActiveLayer {
+				layer,
+				frame: None,
+				next_update: Some(now),
+			})
+			.collect();
+		Self { layers, dimension }
+	}
+
+	fn compose(&self) -> anyhow::Result<RgbaImage> {
+		let mut frame = RgbaImage::new(self.dimension.width.try_into()?, self.dimension.height.try_into()?);
+		for active in &self.layers {
+			if let Some(layer_frame) = &active.frame {
+				draw(&mut frame, layer_frame, active.layer.offset, active.layer.opacity);
+			}
+		}
+		Ok(frame)
+	}
+}
+
+impl FrameSource for Compositor {
+	fn next_frame(&mut self) -> anyhow::Result<Option<DynamicImage>> {
+		let now = Instant::now();
+		let mut changed = false;
+		for active in self
+			.layers
+			.iter_mut()
+			.filter(|active| active.next_update.is_some_and(|next| next <= now))
+		{
+			let layer = &mut active.layer;
+			let next_frame = layer
+				.source
+				.next_frame()
+				.with_context(|| format!("Layer {} failed to produce a frame.", layer.name))?;
+			if let Some(frame) = next_frame {
+				active.frame = Some(resize_frame(
+					&frame,
+					layer.resize_type,
+					layer.resize_filter,
+					layer.dimension.width.try_into()?,
+					layer.dimension.height.try_into()?,
+				));
+				changed = true;
+			}
+			active.next_update = layer.source.interval().map(|interval| now + interval);
+		}
+
+		if !changed {
+			return Ok(None);
+		}
+		Ok(Some(DynamicImage::ImageRgba8(self.compose()?)))
+	}
+
+	fn interval(&self) -> Option<Duration> {
+		self.layers
+			.iter()
+			.filter_map(|active| active.layer.source.interval())
+			.min()
+	}
+}
+
+/// Draws `layer` with its top left corner at `offset` over `frame`, cutting off whatever is outside.
+fn draw(frame: &mut RgbaImage, layer: &RgbaImage, offset: Coordinate, opacity: f32) {
+	let opacity = f64::from(opacity.clamp(0.0, 1.0));
+	for (x, y, pixel) in layer.enumerate_pixels() {
+		let x = i64::from(x) + offset.x;
+		let y = i64::from(y) + offset.y;
+		if x < 0 || y < 0 || x >= i64::from(frame.width()) || y >= i64::from(frame.height()) {
+			continue;
+		}
+
+		let alpha = f64::from(pixel[3]) / 255.0 * opacity;
+		if alpha <= 0.0 {
+			continue;
+		}
+		let premultiplied = [
+			f64::from(pixel[0]) * alpha,
+			f64::from(pixel[1]) * alpha,
+			f64::from(pixel[2]) * alpha,
+			alpha,
+		];
+		let target = frame.get_pixel_mut(x as u32, y as u32);
+		*target = over(premultiplied, *target);
+	}
+}
@@ -1,19 +1,19 @@
 use serde::Deserialize;
-use std::convert::TryInto;
 use std::fmt::{Display, Formatter};
 use std::ops::{Add, AddAssign};
 
+/// Position on the canvas, negative values are off the left or top edge.
 #[derive(Copy, Clone, Default, Debug, Deserialize)]
 pub struct Coordinate {
-	pub x: usize,
-	pub y: usize,
+	pub x: i64,
+	pub y: i64,
 }
 
 impl Coordinate {
 	pub fn new(x: u32, y: u32) -> Self {
 		Self {
-			x: x.try_into().unwrap(),
-			y: y.try_into().unwrap(),
+			x: i64::from(x),
+			y: i64::from(y),
 		}
 	}
 }
@@ -43,7 +43,7 @@ impl Display for Coordinate {
 	}
 }
 
-#[derive(Copy, Clone, Debug, Deserialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
 pub struct Dimension {
 	pub width: usize,
 	pub height: usize,
@@ -53,6 +53,11 @@ impl Dimension {
 	pub fn pixels(self) -> usize {
 		self.width * self.height
 	}
+
+	/// Whether `coordinate` is inside a canvas of this size.
+	pub fn contains(self, coordinate: Coordinate) -> bool {
+		(0..self.width as i64).contains(&coordinate.x) && (0..self.height as i64).contains(&coordinate.y)
+	}
 }
 
 impl Coordinate {
@@ -0,0 +1,177 @@
+use serde::de::{Error, Visitor};
+use serde::{Deserialize, Deserializer};
+use std::fmt::Formatter;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+use std::str::FromStr;
+
+/// Unevaluated sum of two `f64`, which gives about 32 significant decimal digits instead of 16.
+///
+/// Used for the reference orbit of deep zooms, where the coordinates need more precision than `f64` has.
+#[derive(Copy, Clone, Debug, Default, PartialEq, PartialOrd)]
+pub struct DoubleDouble {
+	high: f64,
+	/// Rounding error of `high`, always smaller than half an ulp of it
+	low: f64,
+}
+
+impl DoubleDouble {
+	const TEN: DoubleDouble = DoubleDouble { high: 10.0, low: 0.0 };
+}
+
+impl From<f64> for DoubleDouble {
+	fn from(value: f64) -> Self {
+		Self { high: value, low: 0.0 }
+	}
+}
+
+impl From<DoubleDouble> for f64 {
+	fn from(value: DoubleDouble) -> Self {
+		value.high + value.low
+	}
+}
+
+/// Sum of `a` and `b` together with its rounding error.
+fn two_sum(a: f64, b: f64) -> (f64, f64) {
+	let sum = a + b;
+	let b_part = sum - a;
+	(sum, (a - (sum - b_part)) + (b - b_part))
+}
+
+/// Like `two_sum`, but only correct if `abs(a) >= abs(b)`.
+fn quick_two_sum(a: f64, b: f64) -> DoubleDouble {
+	let sum = a + b;
+	DoubleDouble {
+		high: sum,
+		low: b - (sum - a),
+	}
+}
+
+impl Add for DoubleDouble {
+	type Output = DoubleDouble;
+
+	fn add(self, other: DoubleDouble) -> DoubleDouble {
+		let (high, high_error) = two_sum(self.high, other.high);
+		let (low, low_error) = two_sum(self.low, other.low);
+		let sum = quick_two_sum(high, high_error + low);
+		quick_two_sum(sum.high, sum.low + low_error)
+	}
+}
+
+impl Sub for DoubleDouble {
+	type Output = DoubleDouble;
+
+	fn sub(self, other: DoubleDouble) -> DoubleDouble {
+		self + -other
+	}
+}
+
+impl Neg for DoubleDouble {
+	type Output = DoubleDouble;
+
+	fn neg(self) -> DoubleDouble {
+		DoubleDouble {
+			high: -self.high,
+			low: -self.low,
+		}
+	}
+}
+
+impl Mul for DoubleDouble {
+	type Output = DoubleDouble;
+
+	fn mul(self, other: DoubleDouble) -> DoubleDouble {
+		let product = self.high * other.high;
+		// the fused multiply add gives the exact rounding error of the product
+		let error = self.high.mul_add(other.high, -product);
+		quick_two_sum(product, error + self.high * other.low + self.low * other.high)
+	}
+}
+
+impl Div for DoubleDouble {
+	type Output = DoubleDouble;
+
+	fn div(self, other: DoubleDouble) -> DoubleDouble {
+		// long division, every step fixes the error of the previous quotient
+		let first = self.high / other.high;
+		let remainder = self - other * DoubleDouble::from(first);
+		let second = remainder.high / other.high;
+		let remainder = remainder - other * DoubleDouble::from(second);
+		let third = remainder.high / other.high;
+		quick_two_sum(first, second) + DoubleDouble::from(third)
+	}
+}
+
+/// Largest decimal exponent `from_str` accepts.
+const MAX_EXPONENT: i32 = 400;
+
+impl FromStr for DoubleDouble {
+	type Err = String;
+
+	/// Parses decimal numbers like `-1.74908637481494140001e-3` with all of their digits.
+	fn from_str(text: &str) -> Result<Self, Self::Err> {
+		let invalid = || format!("Invalid number '{text}'");
+		let (mantissa, exponent) = match text.find(['e', 'E']) {
+			Some(index) => (&text[..index], text[index + 1..].parse::<i32>().map_err(|_| invalid())?),
+			None => (text, 0),
+		};
+		// Far outside of the range of f64 anyway, and scaling by it would take forever
+		if !(-MAX_EXPONENT..=MAX_EXPONENT).contains(&exponent) {
+			return Err(format!("Exponent of '{text}' is out of range"));
+		}
+		let (negative, mantissa) = match mantissa.strip_prefix('-') {
+			Some(mantissa) => (true, mantissa),
+			None => (false, mantissa.strip_prefix('+').unwrap_or(mantissa)),
+		};
+		let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+		if integer.is_empty() && fraction.is_empty() {
+			return Err(invalid());
+		}
+
+		let mut value = DoubleDouble::default();
+		for digit in integer.chars().chain(fraction.chars()) {
+			let digit = digit.to_digit(10).ok_or_else(invalid)?;
+			value = value * DoubleDouble::TEN + DoubleDouble::from(f64::from(digit));
+		}
+
+		let exponent = exponent - fraction.len() as i32;
+		let mut scale = DoubleDouble::from(1.0);
+		for _ in 0..exponent.unsigned_abs() {
+			scale = scale * DoubleDouble::TEN;
+		}
+		value = if exponent < 0 { value / scale } else { value * scale };
+		Ok(if negative { -value } else { value })
+	}
+}
+
+impl<'de> Deserialize<'de> for DoubleDouble {
+	/// Accepts plain numbers, or strings for coordinates with more digits than `f64` can hold.
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		struct DoubleDoubleVisitor;
+
+		impl Visitor<'_> for DoubleDoubleVisitor {
+			type Value = DoubleDouble;
+
+			fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+				formatter.write_str("a number or a string containing a number")
+			}
+
+			fn visit_f64<E: Error>(self, value: f64) -> Result<Self::Value, E> {
+				Ok(value.into())
+			}
+
+			fn visit_i64<E: Error>(self, value: i64) -> Result<Self::Value, E> {
+				Ok((value as f64).into())
+			}
+
+			fn visit_u64<E: Error>(self, value: u64) -> Result<Self::Value, E> {
+				Ok((value as f64).into())
+			}
+
+			fn visit_str<E: Error>(self, value: &str) -> Result<Self::Value, E> {
+				value.parse().map_err(E::custom)
+			}
+		}
+
+		deserializer.deserialize_any(DoubleDoubleVisitor)
+	}
+}
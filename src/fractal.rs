@@ -1,11 +1,25 @@
-use crate::complex::abs;
 use crate::complex::Complex;
+use crate::double_double::DoubleDouble;
+use crate::fractal::deep_zoom::ReferenceOrbit;
+use crate::fractal::formula::Formula;
+use crate::settings;
+use crate::settings::Style;
+use anyhow::{bail, Context};
+use image::{Rgba, RgbaImage};
+use rayon::prelude::*;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Instant;
+
+pub mod animation;
+pub mod coloring;
+pub mod deep_zoom;
+pub mod formula;
 
 pub fn mandelbrot(c: Complex, iterations: u32) -> f64 {
 	let mut z = Complex { real: 0.0, imag: 0.0 };
 	for i in 0..iterations {
-		if abs(z) > 2.0 {
-			return i as f64 / iterations as f64;
+		if z.norm_sqr() > BAILOUT * BAILOUT {
+			return smooth_escape(i, z, 2.0, iterations);
 		}
 		z = z * z + c;
 	}
@@ -17,8 +31,8 @@ pub fn julia(z: Complex, initial_value: Complex, iterations: u32) -> f64 {
 	let mut z = z;
 	let c = initial_value;
 	for i in 0..iterations {
-		if abs(z) > 4.0 {
-			return i as f64 / iterations as f64;
+		if z.norm_sqr() > BAILOUT * BAILOUT {
+			return smooth_escape(i, z, 2.0, iterations);
 		}
 
 		z = z * z + c;
@@ -26,3 +40,293 @@ pub fn julia(z: Complex, initial_value: Complex, iterations: u32) -> f64 {
 
 	1.0
 }
+
+pub fn burning_ship(c: Complex, iterations: u32) -> f64 {
+	let mut z = Complex { real: 0.0, imag: 0.0 };
+	for i in 0..iterations {
+		if z.norm_sqr() > BAILOUT * BAILOUT {
+			return smooth_escape(i, z, 2.0, iterations);
+		}
+		let folded = Complex {
+			real: z.real.abs(),
+			imag: z.imag.abs(),
+		};
+		z = folded * folded + c;
+	}
+
+	1.0
+}
+
+pub fn tricorn(c: Complex, iterations: u32) -> f64 {
+	let mut z = Complex { real: 0.0, imag: 0.0 };
+	for i in 0..iterations {
+		if z.norm_sqr() > BAILOUT * BAILOUT {
+			return smooth_escape(i, z, 2.0, iterations);
+		}
+		z = z.conj() * z.conj() + c;
+	}
+
+	1.0
+}
+
+pub fn multibrot(c: Complex, exponent: u32, iterations: u32) -> f64 {
+	let mut z = Complex { real: 0.0, imag: 0.0 };
+	for i in 0..iterations {
+		if z.norm_sqr() > BAILOUT * BAILOUT {
+			return smooth_escape(i, z, f64::from(exponent), iterations);
+		}
+		z = power(z, exponent) + c;
+	}
+
+	1.0
+}
+
+/// Newton's method for `polynomial` starting at `z`.
+///
+/// Every root gets its own band of values, shaded by how fast it was reached. Points that don't converge return 0.
+pub fn newton(z: Complex, polynomial: &Polynomial, iterations: u32) -> f64 {
+	let mut z = z;
+	for i in 0..iterations {
+		if let Some(index) = polynomial
+			.roots
+			.iter()
+			.position(|root| (z - *root).norm_sqr() < NEWTON_TOLERANCE * NEWTON_TOLERANCE)
+		{
+			let speed = 1.0 - (f64::from(i) + 1.0) / (f64::from(iterations) + 1.0);
+			return (index as f64 + speed) / polynomial.roots.len() as f64;
+		}
+		z = z - evaluate(&polynomial.coefficients, z) / evaluate(&polynomial.derivative, z);
+		// the derivative was zero
+		if !z.real.is_finite() || !z.imag.is_finite() {
+			return 0.0;
+		}
+	}
+
+	0.0
+}
+
+const NEWTON_TOLERANCE: f64 = 1e-6;
+
+/// Escape radius, much bigger than needed for the set itself so the smooth coloring has no visible bands
+pub(crate) const BAILOUT: f64 = 256.0;
+
+/// Normalized iteration count of a point that escaped in iteration `i`, scaled to `0.0..1.0`.
+pub(crate) fn smooth_escape(i: u32, z: Complex, exponent: f64, iterations: u32) -> f64 {
+	// ln(abs(z)) without the square root
+	let smooth = f64::from(i) + 1.0 - (z.norm_sqr().ln() / 2.0).ln() / exponent.ln();
+	// 1.0 is reserved for points inside the set
+	(smooth / f64::from(iterations)).clamp(0.0, 1.0 - f64::EPSILON)
+}
+
+fn power(base: Complex, exponent: u32) -> Complex {
+	let mut result = Complex { real: 1.0, imag: 0.0 };
+	for _ in 0..exponent {
+		result *= base;
+	}
+	result
+}
+
+/// Horner's method, `coefficients` start with the highest degree.
+fn evaluate(coefficients: &[Complex], z: Complex) -> Complex {
+	coefficients
+		.iter()
+		.fold(Complex { real: 0.0, imag: 0.0 }, |result, coefficient| {
+			result * z + *coefficient
+		})
+}
+
+/// Polynomial for Newton fractals together with its derivative and roots.
+#[derive(Clone, Debug)]
+pub struct Polynomial {
+	/// Starting with the highest degree
+	coefficients: Vec<Complex>,
+	derivative: Vec<Complex>,
+	roots: Vec<Complex>,
+}
+
+impl Polynomial {
+	pub fn new(coefficients: &[Complex]) -> anyhow::Result<Self> {
+		let coefficients = coefficients
+			.iter()
+			.skip_while(|coefficient| coefficient.real == 0.0 && coefficient.imag == 0.0)
+			.copied()
+			.collect::<Vec<_>>();
+		if coefficients.len() < 2 {
+			bail!("Newton fractals need a polynomial of at least degree 1.");
+		}
+
+		let degree = coefficients.len() - 1;
+		let derivative = coefficients[..degree]
+			.iter()
+			.enumerate()
+			.map(|(index, coefficient)| {
+				let factor = (degree - index) as f64;
+				Complex {
+					real: coefficient.real * factor,
+					imag: coefficient.imag * factor,
+				}
+			})
+			.collect();
+		let roots = durand_kerner(&coefficients);
+		Ok(Self {
+			coefficients,
+			derivative,
+			roots,
+		})
+	}
+}
+
+/// Finds all roots of the polynomial at once with the Durand-Kerner method.
+fn durand_kerner(coefficients: &[Complex]) -> Vec<Complex> {
+	let leading = coefficients[0];
+	let monic = coefficients
+		.iter()
+		.map(|coefficient| *coefficient / leading)
+		.collect::<Vec<_>>();
+	let degree = monic.len() - 1;
+
+	// the usual starting values, powers of a number that is neither real nor a root of unity
+	let seed = Complex { real: 0.4, imag: 0.9 };
+	let mut roots = (0..degree as u32).map(|index| power(seed, index)).collect::<Vec<_>>();
+	for _ in 0..500 {
+		for index in 0..degree {
+			let denominator = (0..degree)
+				.filter(|other| *other != index)
+				.fold(Complex { real: 1.0, imag: 0.0 }, |product, other| {
+					product * (roots[index] - roots[other])
+				});
+			roots[index] = roots[index] - evaluate(&monic, roots[index]) / denominator;
+		}
+	}
+	roots
+}
+
+#[derive(Clone, Debug)]
+pub enum FractalType {
+	Mandelbrot,
+	Julia { initial_value: Complex },
+	BurningShip,
+	Tricorn,
+	Multibrot { exponent: u32 },
+	Newton(Polynomial),
+	Formula(Formula),
+}
+
+impl FractalType {
+	/// The fractal for `style`, fails for styles that aren't fractals.
+	pub fn new(style: &Style, settings: &settings::Fractal) -> anyhow::Result<Self> {
+		Ok(match style {
+			_ if settings.deep_zoom && !matches!(style, Style::Mandelbrot) => {
+				bail!("Deep zoom is only supported for the mandelbrot style.")
+			}
+			Style::Mandelbrot => FractalType::Mandelbrot,
+			Style::Julia => FractalType::Julia {
+				initial_value: settings.initial_value,
+			},
+			Style::BurningShip => FractalType::BurningShip,
+			Style::Tricorn => FractalType::Tricorn,
+			Style::Multibrot => {
+				let exponent = settings.multibrot.exponent;
+				if exponent < 2 {
+					bail!("Invalid multibrot exponent {exponent}, expected at least 2.");
+				}
+				FractalType::Multibrot { exponent }
+			}
+			Style::Newton => FractalType::Newton(Polynomial::new(&settings.newton.coefficients)?),
+			Style::Formula => {
+				let formula = settings
+					.formula
+					.as_ref()
+					.context("Missing formula in [fractal] for formula style.")?;
+				let julia = settings.formula_julia.then_some(settings.initial_value);
+				FractalType::Formula(Formula::new(formula, settings.bailout, julia)?)
+			}
+			_ => bail!("Not a fractal!"),
+		})
+	}
+
+	fn escape(&self, point: Complex, iterations: u32) -> f64 {
+		match self {
+			FractalType::Mandelbrot => mandelbrot(point, iterations),
+			FractalType::Julia { initial_value } => julia(point, *initial_value, iterations),
+			FractalType::BurningShip => burning_ship(point, iterations),
+			FractalType::Tricorn => tricorn(point, iterations),
+			FractalType::Multibrot { exponent } => multibrot(point, *exponent, iterations),
+			FractalType::Newton(polynomial) => newton(point, polynomial, iterations),
+			FractalType::Formula(formula) => formula.escape(point, iterations),
+		}
+	}
+}
+
+/// Part of the complex plane that is shown.
+#[derive(Clone, Copy, Debug)]
+pub struct View {
+	/// Point in the middle of the frame
+	pub center: Complex<DoubleDouble>,
+	/// Width of the visible area, the height follows from the aspect ratio of the frame
+	pub width: f64,
+}
+
+impl View {
+	/// Distance from the center to the center of the pixel at `x`/`y` of a `width`x`height` frame.
+	fn offset(&self, x: u32, y: u32, width: u32, height: u32) -> Complex {
+		let pixel_size = self.width / f64::from(width);
+		Complex {
+			real: (f64::from(x) + 0.5 - f64::from(width) / 2.0) * pixel_size,
+			imag: (f64::from(y) + 0.5 - f64::from(height) / 2.0) * pixel_size,
+		}
+	}
+}
+
+/// Renders are reported on the console from this number of pixels on.
+const REPORT_PIXELS: u32 = 1_000_000;
+
+/// Renders the fractal with all cores, one row at a time.
+///
+/// With `report` set, progress and timing of big renders are printed.
+pub fn render(
+	fractal: &FractalType,
+	view: View,
+	settings: &settings::Fractal,
+	width: u32,
+	height: u32,
+	report: bool,
+) -> RgbaImage {
+	let report = report && width.saturating_mul(height) >= REPORT_PIXELS;
+	let start = Instant::now();
+	let finished_rows = AtomicU32::new(0);
+
+	let center = Complex::<f64>::from(view.center);
+	let reference = settings
+		.deep_zoom
+		.then(|| ReferenceOrbit::mandelbrot(view.center, settings.iterations));
+
+	let mut values = vec![0.0; width as usize * height as usize];
+	values
+		.par_chunks_mut(width.max(1) as usize)
+		.enumerate()
+		.for_each(|(y, row)| {
+			for (x, value) in row.iter_mut().enumerate() {
+				let offset = view.offset(x as u32, y as u32, width, height);
+				*value = match &reference {
+					Some(reference) => reference.escape(offset, settings.iterations),
+					None => fractal.escape(center + offset, settings.iterations),
+				};
+			}
+
+			if report {
+				let finished = finished_rows.fetch_add(1, Ordering::Relaxed) + 1;
+				let percent = finished * 100 / height;
+				if percent / 10 != (finished - 1) * 100 / height / 10 {
+					println!("Rendering fractal: {percent}%");
+				}
+			}
+		});
+
+	let colors = settings.coloring.colorize(&values, settings.active_threshold);
+	let pixels = colors.into_iter().flat_map(|color| Rgba::from(color).0).collect();
+	if report {
+		println!("Rendered {width}x{height} fractal in {:.2?}", start.elapsed());
+	}
+	RgbaImage::from_raw(width, height, pixels).expect("One color per pixel")
+}
@@ -0,0 +1,139 @@
+use super::{render, FractalType, View};
+use crate::complex::Complex;
+use crate::double_double::DoubleDouble;
+use crate::frame_source::{frame_interval, FrameSource};
+use crate::settings;
+use crate::settings::FractalAnimation;
+use anyhow::bail;
+use image::DynamicImage;
+use std::time::{Duration, Instant};
+
+/// Renders a fractal continuously while zooming into it or moving the Julia parameter along a path.
+pub struct Animation {
+	fractal: FractalType,
+	settings: settings::Fractal,
+	motion: Motion,
+	width: u32,
+	height: u32,
+	interval: Duration,
+	start: Instant,
+}
+
+enum Motion {
+	Zoom {
+		target: Complex<DoubleDouble>,
+		speed: f64,
+		max_zoom: f64,
+	},
+	JuliaSweep {
+		path: Vec<Complex>,
+		/// Length of the whole closed path
+		length: f64,
+		period: f64,
+	},
+}
+
+impl Animation {
+	pub fn new(
+		fractal: FractalType,
+		settings: settings::Fractal,
+		animation: FractalAnimation,
+		width: u32,
+		height: u32,
+	) -> anyhow::Result<Self> {
+		let motion = match animation {
+			FractalAnimation::Zoom(zoom) => {
+				let valid = |value: f64| value.is_finite() && value > 1.0;
+				if !valid(zoom.speed) || !valid(zoom.max_zoom) {
+					bail!("Zoom speed and maximum zoom have to be finite and greater than 1.");
+				}
+				Motion::Zoom {
+					target: zoom.target,
+					speed: zoom.speed,
+					max_zoom: zoom.max_zoom,
+				}
+			}
+			FractalAnimation::JuliaSweep(sweep) => {
+				if !matches!(fractal, FractalType::Julia { .. }) {
+					bail!("The Julia sweep only works with the julia style.");
+				}
+				if sweep.path.is_empty() || !(sweep.period.is_finite() && sweep.period > 0.0) {
+					bail!("The Julia sweep needs at least one point and a finite, positive period.");
+				}
+				let length = segments(&sweep.path).map(|(from, to)| (to - from).abs()).sum();
+				Motion::JuliaSweep {
+					path: sweep.path,
+					length,
+					period: sweep.period,
+				}
+			}
+		};
+		Ok(Self {
+			fractal,
+			interval: frame_interval(settings.frame_rate)?,
+			settings,
+			motion,
+			width,
+			height,
+			start: Instant::now(),
+		})
+	}
+}
+
+impl FrameSource for Animation {
+	fn next_frame(&mut self) -> anyhow::Result<Option<DynamicImage>> {
+		let elapsed = self.start.elapsed().as_secs_f64();
+		let mut fractal = self.fractal.clone();
+		let mut view = View {
+			center: self.settings.center,
+			width: self.settings.width,
+		};
+
+		match &self.motion {
+			Motion::Zoom {
+				target,
+				speed,
+				max_zoom,
+			} => {
+				// start over once the configured depth is reached
+				let duration = max_zoom.ln() / speed.ln();
+				let zoom = speed.powf(elapsed % duration);
+				view = View {
+					center: *target,
+					width: self.settings.width / zoom,
+				};
+			}
+			Motion::JuliaSweep { path, length, period } => {
+				let progress = (elapsed % period) / period;
+				fractal = FractalType::Julia {
+					initial_value: point_on_path(path, *length * progress),
+				};
+			}
+		}
+
+		let frame = render(&fractal, view, &self.settings, self.width, self.height, false);
+		Ok(Some(DynamicImage::ImageRgba8(frame)))
+	}
+
+	fn interval(&self) -> Option<Duration> {
+		Some(self.interval)
+	}
+}
+
+/// Line segments of the closed path, the last point is connected to the first one.
+fn segments(path: &[Complex]) -> impl Iterator<Item = (Complex, Complex)> + '_ {
+	path.iter()
+		.zip(path.iter().cycle().skip(1))
+		.map(|(from, to)| (*from, *to))
+}
+
+fn point_on_path(path: &[Complex], mut distance: f64) -> Complex {
+	for (from, to) in segments(path) {
+		let length = (to - from).abs();
+		if distance <= length && length > 0.0 {
+			return from + (to - from) * (distance / length);
+		}
+		distance -= length;
+	}
+	path[0]
+}
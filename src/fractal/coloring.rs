@@ -0,0 +1,176 @@
+use crate::pixel::Color;
+use serde::Deserialize;
+
+/// Maps the escape values of a fractal to colors.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Coloring {
+	pub gradient: Gradient,
+	/// Repeat the gradient `cycles` times instead of stretching it over all values once
+	pub cyclic: bool,
+	pub cycles: f64,
+	/// Shifts cyclic gradients, 1.0 is a full cycle
+	pub offset: f64,
+	/// Spread the colors evenly over the pixels instead of the escape values
+	pub histogram: bool,
+	/// Color of points inside the set
+	pub inside: Color,
+}
+
+impl Default for Coloring {
+	fn default() -> Self {
+		Self {
+			gradient: Gradient::Named(NamedGradient::Ultra),
+			cyclic: false,
+			cycles: 1.0,
+			offset: 0.0,
+			histogram: false,
+			inside: Color::rgb(0, 0, 0),
+		}
+	}
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Gradient {
+	Named(NamedGradient),
+	/// Colors at positions from 0.0 to 1.0, interpolated linearly in between
+	Stops(Vec<ColorStop>),
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NamedGradient {
+	/// Blue, white and orange, the look of most fractal programs
+	Ultra,
+	Fire,
+	Ocean,
+	Grayscale,
+	Rainbow,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct ColorStop {
+	pub position: f64,
+	pub color: Color,
+}
+
+impl NamedGradient {
+	fn stops(self) -> Vec<ColorStop> {
+		let stops: &[(f64, [u8; 3])] = match self {
+			NamedGradient::Ultra => &[
+				(0.0, [0, 7, 100]),
+				(0.16, [32, 107, 203]),
+				(0.42, [237, 255, 255]),
+				(0.6425, [255, 170, 0]),
+				(0.8575, [0, 2, 0]),
+				(1.0, [0, 7, 100]),
+			],
+			NamedGradient::Fire => &[
+				(0.0, [0, 0, 0]),
+				(0.3, [180, 0, 0]),
+				(0.6, [255, 140, 0]),
+				(0.85, [255, 240, 60]),
+				(1.0, [255, 255, 255]),
+			],
+			NamedGradient::Ocean => &[
+				(0.0, [0, 0, 40]),
+				(0.4, [0, 60, 160]),
+				(0.75, [0, 190, 200]),
+				(1.0, [230, 255, 255]),
+			],
+			NamedGradient::Grayscale => &[(0.0, [0, 0, 0]), (1.0, [255, 255, 255])],
+			NamedGradient::Rainbow => &[
+				(0.0, [255, 0, 0]),
+				(1.0 / 6.0, [255, 255, 0]),
+				(2.0 / 6.0, [0, 255, 0]),
+				(3.0 / 6.0, [0, 255, 255]),
+				(4.0 / 6.0, [0, 0, 255]),
+				(5.0 / 6.0, [255, 0, 255]),
+				(1.0, [255, 0, 0]),
+			],
+		};
+		stops
+			.iter()
+			.map(|(position, [red, green, blue])| ColorStop {
+				position: *position,
+				color: Color::rgb(*red, *green, *blue),
+			})
+			.collect()
+	}
+}
+
+impl Gradient {
+	fn stops(&self) -> Vec<ColorStop> {
+		let mut stops = match self {
+			Gradient::Named(gradient) => gradient.stops(),
+			Gradient::Stops(stops) => stops.clone(),
+		};
+		stops.sort_by(|a, b| a.position.total_cmp(&b.position));
+		stops
+	}
+}
+
+impl Coloring {
+	/// Colors for the escape values of all pixels, values below `threshold` are transparent and 1.0 is inside the set.
+	pub fn colorize(&self, values: &[f64], threshold: f64) -> Vec<Color> {
+		let stops = self.gradient.stops();
+		let outside = |value: f64| value >= threshold && value < 1.0;
+
+		// sorted escape values, the position of a value in here is its share of the pixels
+		let histogram = self.histogram.then(|| {
+			let mut sorted = values
+				.iter()
+				.copied()
+				.filter(|value| outside(*value))
+				.collect::<Vec<_>>();
+			sorted.sort_by(f64::total_cmp);
+			sorted
+		});
+
+		values
+			.iter()
+			.map(|value| {
+				if *value < threshold {
+					return Color::null();
+				}
+				if !outside(*value) {
+					return self.inside;
+				}
+
+				let value = match &histogram {
+					Some(sorted) => sorted.partition_point(|other| other < value) as f64 / sorted.len() as f64,
+					None => *value,
+				};
+				let position = if self.cyclic {
+					(value * self.cycles + self.offset).rem_euclid(1.0)
+				} else {
+					value
+				};
+				interpolate(&stops, position)
+			})
+			.collect()
+	}
+}
+
+fn interpolate(stops: &[ColorStop], position: f64) -> Color {
+	let Some(first) = stops.first() else {
+		return Color::null();
+	};
+	let Some(next_index) = stops.iter().position(|stop| stop.position > position) else {
+		return stops[stops.len() - 1].color;
+	};
+	if next_index == 0 {
+		return first.color;
+	}
+
+	let (from, to) = (stops[next_index - 1], stops[next_index]);
+	let fraction = (position - from.position) / (to.position - from.position);
+	let mix = |from: u8, to: u8| (f64::from(from) + (f64::from(to) - f64::from(from)) * fraction).round() as u8;
+	Color::rgba(
+		mix(from.color.red(), to.color.red()),
+		mix(from.color.green(), to.color.green()),
+		mix(from.color.blue(), to.color.blue()),
+		mix(from.color.alpha(), to.color.alpha()),
+	)
+}
@@ -0,0 +1,58 @@
+use super::{smooth_escape, BAILOUT};
+use crate::complex::Complex;
+use crate::double_double::DoubleDouble;
+
+/// Orbit of the center of the view, calculated with double-double precision.
+///
+/// Every pixel is only iterated as a small difference to this orbit (perturbation), which works in `f64` even when
+/// the pixels are too close together to be told apart in `f64` themselves.
+pub struct ReferenceOrbit {
+	/// Rounded to `f64`, the deltas of the pixels are tiny compared to these
+	orbit: Vec<Complex>,
+}
+
+impl ReferenceOrbit {
+	pub fn mandelbrot(center: Complex<DoubleDouble>, iterations: u32) -> Self {
+		let mut orbit = Vec::with_capacity(iterations as usize + 1);
+		let mut z = Complex::<DoubleDouble>::from(Complex { real: 0.0, imag: 0.0 });
+		orbit.push(Complex::from(z));
+		for _ in 0..iterations {
+			z = z * z + center;
+			let rounded = Complex::from(z);
+			orbit.push(rounded);
+			if rounded.norm_sqr() > BAILOUT * BAILOUT {
+				break;
+			}
+		}
+		Self { orbit }
+	}
+
+	/// Mandelbrot escape value of the point `delta_c` away from the center.
+	pub fn escape(&self, delta_c: Complex, iterations: u32) -> f64 {
+		let mut delta_z = Complex { real: 0.0, imag: 0.0 };
+		let mut reference_index = 0;
+		for i in 0..iterations {
+			let reference = self.orbit[reference_index];
+			let z = reference + delta_z;
+			if z.norm_sqr() > BAILOUT * BAILOUT {
+				return smooth_escape(i, z, 2.0, iterations);
+			}
+
+			// Rebase onto the start of the orbit when the pixel gets closer to zero than to the reference or the
+			// reference has escaped already. This avoids the glitches perturbation is known for.
+			let (reference, next_index) =
+				if z.norm_sqr() < delta_z.norm_sqr() || reference_index == self.orbit.len() - 1 {
+					delta_z = z;
+					(self.orbit[0], 1)
+				} else {
+					(reference, reference_index + 1)
+				};
+
+			// z² + c = (Z + δz)² + C + δc, minus the orbit Z² + C
+			delta_z = (reference + reference + delta_z) * delta_z + delta_c;
+			reference_index = next_index;
+		}
+
+		1.0
+	}
+}
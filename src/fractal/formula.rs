@@ -0,0 +1,447 @@
+use super::smooth_escape;
+use crate::complex::Complex;
+use anyhow::{bail, Context};
+use std::f64::consts::{E, PI};
+
+/// Iteration formula from the config like `z^3 + c*sin(z)`, parsed once and evaluated for every iteration.
+///
+/// Supports `z`, `c`, numbers, the constants `i`, `pi` and `e`, the operators `+ - * / ^`, parentheses and the
+/// functions `sin`, `cos`, `exp`, `ln`, `sqrt`, `conj` and `abs`.
+#[derive(Clone, Debug)]
+pub struct Formula {
+	expression: Expression,
+	bailout: f64,
+	/// Degree of the formula in `z` for the smooth coloring, 2 if it isn't a polynomial of a higher degree
+	degree: f64,
+	/// `c` of Julia style iterations, `None` for Mandelbrot style where `c` is the pixel
+	julia: Option<Complex>,
+}
+
+#[derive(Clone, Debug)]
+enum Expression {
+	Constant(Complex),
+	Z,
+	C,
+	Negate(Box<Expression>),
+	Binary(Operator, Box<Expression>, Box<Expression>),
+	Function(Function, Box<Expression>),
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Operator {
+	Add,
+	Subtract,
+	Multiply,
+	Divide,
+	Power,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Function {
+	Sin,
+	Cos,
+	Exp,
+	Ln,
+	Sqrt,
+	Conj,
+	Abs,
+}
+
+impl Formula {
+	pub fn new(text: &str, bailout: f64, julia: Option<Complex>) -> anyhow::Result<Self> {
+		if !(bailout.is_finite() && bailout > 0.0) {
+			bail!("Invalid bailout {bailout}, expected a finite, positive escape radius");
+		}
+		let expression = Parser::new(text)
+			.parse()
+			.with_context(|| format!("Invalid formula '{text}'"))?;
+		let degree = match expression.degree() {
+			Some(degree) if degree.is_finite() && degree > 1.0 => degree,
+			_ => 2.0,
+		};
+		Ok(Self {
+			expression,
+			bailout,
+			degree,
+			julia,
+		})
+	}
+
+	pub fn escape(&self, point: Complex, iterations: u32) -> f64 {
+		let (mut z, c) = match self.julia {
+			Some(c) => (point, c),
+			None => (Complex::new(0.0, 0.0), point),
+		};
+		for i in 0..iterations {
+			let norm_sqr = z.norm_sqr();
+			// overflows count as escaped, functions like `exp` and `sin` grow fast enough for that
+			if !norm_sqr.is_finite() {
+				return f64::from(i) / f64::from(iterations);
+			}
+			if norm_sqr > self.bailout * self.bailout {
+				return smooth_escape(i, z, self.degree, iterations);
+			}
+			z = self.expression.evaluate(z, c);
+		}
+
+		1.0
+	}
+}
+
+impl Expression {
+	/// Highest power of `z`, `None` if it isn't a polynomial like for `exp(z)`.
+	fn degree(&self) -> Option<f64> {
+		match self {
+			Expression::Constant(_) | Expression::C => Some(0.0),
+			Expression::Z => Some(1.0),
+			Expression::Negate(operand) => operand.degree(),
+			Expression::Binary(operator, left, right) => match (operator, right.as_ref()) {
+				(Operator::Power, Expression::Constant(exponent)) if exponent.imag == 0.0 => {
+					Some(left.degree()? * exponent.real)
+				}
+				(Operator::Power, _) => None,
+				(Operator::Add | Operator::Subtract, _) => Some(left.degree()?.max(right.degree()?)),
+				(Operator::Multiply, _) => Some(left.degree()? + right.degree()?),
+				(Operator::Divide, _) => Some(left.degree()? - right.degree()?),
+			},
+			Expression::Function(function, argument) => match function {
+				Function::Conj | Function::Abs => argument.degree(),
+				Function::Sqrt => Some(argument.degree()? / 2.0),
+				Function::Sin | Function::Cos | Function::Exp | Function::Ln => None,
+			},
+		}
+	}
+
+	fn evaluate(&self, z: Complex, c: Complex) -> Complex {
+		match self {
+			Expression::Constant(value) => *value,
+			Expression::Z => z,
+			Expression::C => c,
+			Expression::Negate(operand) => -operand.evaluate(z, c),
+			Expression::Binary(operator, left, right) => {
+				let left = left.evaluate(z, c);
+				match (operator, right.as_ref()) {
+					// integer powers by multiplication are faster and exact, they are by far the most common
+					(Operator::Power, Expression::Constant(exponent))
+						if exponent.imag == 0.0 && exponent.real.fract() == 0.0 && exponent.real.abs() <= 16.0 =>
+					{
+						let mut result = Complex::new(1.0, 0.0);
+						for _ in 0..exponent.real.abs() as u32 {
+							result *= left;
+						}
+						if exponent.real < 0.0 {
+							1.0 / result
+						} else {
+							result
+						}
+					}
+					(operator, right) => {
+						let right = right.evaluate(z, c);
+						match operator {
+							Operator::Add => left + right,
+							Operator::Subtract => left - right,
+							Operator::Multiply => left * right,
+							Operator::Divide => left / right,
+							Operator::Power => left.powc(right),
+						}
+					}
+				}
+			}
+			Expression::Function(function, argument) => {
+				let argument = argument.evaluate(z, c);
+				match function {
+					Function::Sin => argument.sin(),
+					Function::Cos => argument.cos(),
+					Function::Exp => argument.exp(),
+					Function::Ln => argument.ln(),
+					Function::Sqrt => argument.powf(0.5),
+					Function::Conj => argument.conj(),
+					Function::Abs => Complex::new(argument.abs(), 0.0),
+				}
+			}
+		}
+	}
+}
+
+/// Recursive descent parser, from lowest to highest precedence: `+ -`, `* /`, unary `-`, `^`.
+struct Parser<'a> {
+	text: &'a str,
+	position: usize,
+}
+
+impl<'a> Parser<'a> {
+	fn new(text: &'a str) -> Self {
+		Self { text, position: 0 }
+	}
+
+	fn parse(mut self) -> anyhow::Result<Expression> {
+		let expression = self.sum()?;
+		self.skip_whitespace();
+		if let Some(character) = self.peek() {
+			bail!("Unexpected '{character}' at position {}", self.position);
+		}
+		Ok(expression)
+	}
+
+	fn skip_whitespace(&mut self) {
+		let rest = &self.text[self.position..];
+		self.position += rest.len() - rest.trim_start().len();
+	}
+
+	fn peek(&self) -> Option<char> {
+		self.text[self.position..].chars().next()
+	}
+
+	/// Consumes `expected` if it is the next character after whitespace.
+	fn consume(&mut self, expected: char) -> bool {
+		self.skip_whitespace();
+		if self.peek() == Some(expected) {
+			self.position += expected.len_utf8();
+			true
+		} else {
+			false
+		}
+	}
+
+	fn sum(&mut self) -> anyhow::Result<Expression> {
+		let mut expression = self.product()?;
+		loop {
+			let operator = if self.consume('+') {
+				Operator::Add
+			} else if self.consume('-') {
+				Operator::Subtract
+			} else {
+				return Ok(expression);
+			};
+			expression = Expression::Binary(operator, Box::new(expression), Box::new(self.product()?));
+		}
+	}
+
+	fn product(&mut self) -> anyhow::Result<Expression> {
+		let mut expression = self.unary()?;
+		loop {
+			let operator = if self.consume('*') {
+				Operator::Multiply
+			} else if self.consume('/') {
+				Operator::Divide
+			} else {
+				return Ok(expression);
+			};
+			expression = Expression::Binary(operator, Box::new(expression), Box::new(self.unary()?));
+		}
+	}
+
+	fn unary(&mut self) -> anyhow::Result<Expression> {
+		if self.consume('-') {
+			return Ok(Expression::Negate(Box::new(self.unary()?)));
+		}
+		self.power()
+	}
+
+	fn power(&mut self) -> anyhow::Result<Expression> {
+		let base = self.atom()?;
+		if self.consume('^') {
+			// right associative, so z^2^3 is z^(2^3)
+			let exponent = self.unary()?;
+			return Ok(Expression::Binary(Operator::Power, Box::new(base), Box::new(exponent)));
+		}
+		Ok(base)
+	}
+
+	fn atom(&mut self) -> anyhow::Result<Expression> {
+		self.skip_whitespace();
+		let start = self.position;
+		let Some(character) = self.peek() else {
+			bail!("Unexpected end of formula");
+		};
+
+		if self.consume('(') {
+			let expression = self.sum()?;
+			if !self.consume(')') {
+				bail!("Missing ')' for the '(' at position {start}");
+			}
+			return Ok(expression);
+		}
+
+		if character.is_ascii_digit() || character == '.' {
+			let length = self.text[start..]
+				.find(|character: char| !character.is_ascii_digit() && character != '.')
+				.unwrap_or(self.text.len() - start);
+			self.position += length;
+			let number = &self.text[start..self.position];
+			let value = number
+				.parse::<f64>()
+				.with_context(|| format!("Invalid number '{number}' at position {start}"))?;
+			return Ok(Expression::Constant(Complex::new(value, 0.0)));
+		}
+
+		if character.is_ascii_alphabetic() {
+			let length = self.text[start..]
+				.find(|character: char| !character.is_ascii_alphanumeric())
+				.unwrap_or(self.text.len() - start);
+			self.position += length;
+			let name = &self.text[start..self.position];
+			let function = match name {
+				"z" => return Ok(Expression::Z),
+				"c" => return Ok(Expression::C),
+				"i" => return Ok(Expression::Constant(Complex::new(0.0, 1.0))),
+				"pi" => return Ok(Expression::Constant(Complex::new(PI, 0.0))),
+				"e" => return Ok(Expression::Constant(Complex::new(E, 0.0))),
+				"sin" => Function::Sin,
+				"cos" => Function::Cos,
+				"exp" => Function::Exp,
+				"ln" => Function::Ln,
+				"sqrt" => Function::Sqrt,
+				"conj" => Function::Conj,
+				"abs" => Function::Abs,
+				_ => bail!("Unknown name '{name}' at position {start}"),
+			};
+			if !self.consume('(') {
+				bail!("Missing '(' after function '{name}' at position {start}");
+			}
+			let argument = self.sum()?;
+			if !self.consume(')') {
+				bail!("Missing ')' for the call of '{name}' at position {start}");
+			}
+			return Ok(Expression::Function(function, Box::new(argument)));
+		}
+
+		bail!("Unexpected '{character}' at position {start}")
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn evaluate(text: &str, z: (f64, f64), c: (f64, f64)) -> Complex {
+		Parser::new(text)
+			.parse()
+			.unwrap()
+			.evaluate(Complex::from(z), Complex::from(c))
+	}
+
+	fn assert_close(actual: Complex, expected: Complex) {
+		assert!(
+			(actual - expected).abs() < 1e-9,
+			"expected {:?}, got {:?}",
+			expected,
+			actual
+		);
+	}
+
+	fn assert_real(text: &str, expected: f64) {
+		assert_close(evaluate(text, (0.0, 0.0), (0.0, 0.0)), Complex::new(expected, 0.0));
+	}
+
+	fn parse_error(text: &str) -> String {
+		Parser::new(text).parse().unwrap_err().to_string()
+	}
+
+	#[test]
+	fn precedence() {
+		assert_real("1 + 2 * 3", 7.0);
+		assert_real("(1 + 2) * 3", 9.0);
+		assert_real("2 * 3 ^ 2", 18.0);
+		assert_real("1 - 2 - 3", -4.0);
+		assert_real("8 / 4 / 2", 1.0);
+		assert_real("1 + 6 / 2 ^ 2 * 4", 7.0);
+	}
+
+	#[test]
+	fn power_is_right_associative() {
+		assert_real("2 ^ 3 ^ 2", 512.0);
+		assert_real("(2 ^ 3) ^ 2", 64.0);
+	}
+
+	#[test]
+	fn unary_minus() {
+		assert_real("-2 ^ 2", -4.0);
+		assert_real("(-2) ^ 2", 4.0);
+		assert_real("2 ^ -1", 0.5);
+		assert_real("--3", 3.0);
+		assert_real("2 * -3", -6.0);
+		assert_close(evaluate("-z", (1.0, -2.0), (0.0, 0.0)), Complex::new(-1.0, 2.0));
+	}
+
+	#[test]
+	fn variables_and_constants() {
+		assert_close(evaluate("z^2 + c", (1.0, 1.0), (0.5, 0.0)), Complex::new(0.5, 2.0));
+		assert_close(evaluate("i * i", (0.0, 0.0), (0.0, 0.0)), Complex::new(-1.0, 0.0));
+		assert_real("pi", std::f64::consts::PI);
+		assert_real("e", std::f64::consts::E);
+		assert_real(".5 + 1.25", 1.75);
+	}
+
+	#[test]
+	fn functions() {
+		assert_real("sin(pi / 2)", 1.0);
+		assert_real("cos(pi)", -1.0);
+		assert_real("exp(ln(3))", 3.0);
+		assert_real("abs(3 + 4*i)", 5.0);
+		assert_close(evaluate("sqrt(2 * i)", (0.0, 0.0), (0.0, 0.0)), Complex::new(1.0, 1.0));
+		assert_close(evaluate("conj(z)", (1.0, 2.0), (0.0, 0.0)), Complex::new(1.0, -2.0));
+		assert_close(
+			evaluate("c * sin(z)", (1.0, 0.5), (0.0, 1.0)),
+			Complex::new(0.0, 1.0) * Complex::new(1.0, 0.5).sin(),
+		);
+	}
+
+	#[test]
+	fn error_positions() {
+		assert_eq!(parse_error("z + foo(z)"), "Unknown name 'foo' at position 4");
+		assert_eq!(parse_error("z $"), "Unexpected '$' at position 2");
+		assert_eq!(parse_error("z * (z + 1"), "Missing ')' for the '(' at position 4");
+		assert_eq!(parse_error("sin z"), "Missing '(' after function 'sin' at position 0");
+		assert_eq!(
+			parse_error("2 * cos(z"),
+			"Missing ')' for the call of 'cos' at position 4"
+		);
+		assert_eq!(parse_error("z + 1..2"), "Invalid number '1..2' at position 4");
+		assert_eq!(parse_error("z +"), "Unexpected end of formula");
+		assert_eq!(parse_error("z )"), "Unexpected ')' at position 2");
+	}
+
+	#[test]
+	fn integer_powers_match_powc() {
+		let z = Complex::new(0.7, -1.3);
+		for exponent in [-16.0, -3.0, -1.0, 0.0, 1.0, 2.0, 3.0, 7.0, 16.0, 2.5, 17.0] {
+			// built directly, the parser turns negative exponents into a negation
+			let power = Expression::Binary(
+				Operator::Power,
+				Box::new(Expression::Z),
+				Box::new(Expression::Constant(Complex::new(exponent, 0.0))),
+			);
+			let expected = z.powc(Complex::new(exponent, 0.0));
+			let actual = power.evaluate(z, Complex::new(0.0, 0.0));
+			assert!(
+				(actual - expected).abs() < 1e-9 * expected.abs(),
+				"z^{}: expected {:?}, got {:?}",
+				exponent,
+				expected,
+				actual
+			);
+		}
+	}
+
+	#[test]
+	fn degree() {
+		let degree = |text: &str| Formula::new(text, 2.0, None).unwrap().degree;
+		assert_eq!(degree("z^2 + c"), 2.0);
+		assert_eq!(degree("z^3 + c"), 3.0);
+		assert_eq!(degree("z*z*z*z - z + c"), 4.0);
+		assert_eq!(degree("z^5 / z^2 + c"), 3.0);
+		assert_eq!(degree("conj(z)^2 + c"), 2.0);
+		// not polynomials, or no higher degree than linear
+		assert_eq!(degree("c * sin(z)"), 2.0);
+		assert_eq!(degree("z^i + c"), 2.0);
+		assert_eq!(degree("z + c"), 2.0);
+	}
+
+	#[test]
+	fn invalid_bailout() {
+		for bailout in [0.0, -2.0, f64::NAN, f64::INFINITY] {
+			assert!(Formula::new("z^2 + c", bailout, None).is_err());
+		}
+	}
+}
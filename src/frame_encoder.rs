@@ -0,0 +1,69 @@
+use crate::budget::Budget;
+use crate::canvas;
+use crate::frame_serializer::FrameSerializer;
+use crate::pixel_encoder::PixelEncoder;
+use crate::shared_buffer::{BufferPool, SharedBuffer};
+use crate::{Coordinate, Dimension, Pixel};
+use image::RgbaImage;
+use rayon::prelude::*;
+
+/// Serializes frames and encodes them into one buffer of `PX` commands per stream.
+///
+/// Pixels outside of the canvas are clipped before the frame is split between the streams according to the budget.
+///
+/// The buffers are encoded in parallel on the rayon thread pool. Their allocations are reused
+/// across frames once the streams have released them.
+#[derive(Default)]
+pub struct FrameEncoder {
+	encoder: PixelEncoder,
+	pool: BufferPool,
+	pixels: Vec<Pixel>,
+}
+
+impl FrameEncoder {
+	pub fn encode(
+		&mut self,
+		frame: &RgbaImage,
+		serializer: &mut dyn FrameSerializer,
+		budget: &Budget,
+		stream_count: usize,
+		position: Coordinate,
+		canvas_size: Option<Dimension>,
+	) -> Vec<SharedBuffer> {
+		if stream_count == 0 {
+			return Vec::new();
+		}
+
+		let Self { encoder, pool, pixels } = self;
+		pixels.clear();
+		pixels.extend(
+			serializer
+				.serialize(frame)
+				.filter(|pixel| pixel.color.alpha() != 0)
+				.filter(|pixel| canvas::is_visible(pixel.coordinate + position, canvas_size)),
+		);
+		let streams = budget.split(pixels, stream_count);
+		let pixels = &pixels[..];
+		let mut jobs = streams
+			.into_iter()
+			.map(|ranges| {
+				let parts = ranges.into_iter().map(|range| &pixels[range]).collect::<Vec<_>>();
+				(parts, pool.take())
+			})
+			.collect::<Vec<_>>();
+
+		jobs.par_iter_mut()
+			.for_each(|(parts, buffer)| encode_pixels(encoder, parts, position, buffer));
+
+		jobs.into_iter().map(|(_, buffer)| pool.share(buffer)).collect()
+	}
+}
+
+fn encode_pixels(encoder: &PixelEncoder, parts: &[&[Pixel]], position: Coordinate, buffer: &mut Vec<u8>) {
+	let pixel_count = parts.iter().map(|pixels| pixels.len()).sum::<usize>();
+	buffer.reserve(pixel_count * Pixel::BYTE_ESTIMATE);
+	for mut pixel in parts.iter().flat_map(|pixels| pixels.iter()).copied() {
+		pixel.coordinate += position;
+		encoder.encode(pixel, buffer);
+	}
+}
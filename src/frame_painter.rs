@@ -1,55 +1,138 @@
+use crate::alpha::AlphaMode;
+use crate::budget::Budget;
 use crate::frame_serializer::FrameSerializer;
+use crate::quantizer::Quantizer;
+use crate::transform::Transform;
 use crate::{Coordinate, Dimension};
 use image::imageops::FilterType;
 use image::{DynamicImage, GenericImageView, RgbaImage};
+use serde::Deserialize;
 use std::convert::TryInto;
+use std::sync::Arc;
 
 pub mod io_uring;
 
 // TODO: Find better API with better separation of concerns
-pub trait FramePainter {
+pub trait FramePainter: Send {
 	fn update_frame(&mut self, frame: DynamicImage);
 	fn update_dimensions(&mut self, dimensions: Dimension);
 	fn update_resize_type(&mut self, resize_type: ResizeType);
 	fn update_resize_filter(&mut self, resize_filter: FilterType);
 	fn update_position(&mut self, coordinate: Coordinate);
 	fn update_stream_count(&mut self, count: usize);
+	fn update_budget(&mut self, budget: Budget);
 	fn update_serializer(&mut self, serializer: Box<dyn FrameSerializer + 'static>);
+	fn update_quantizer(&mut self, quantizer: Option<Quantizer>);
+	fn update_alpha_mode(&mut self, alpha_mode: AlphaMode);
+	fn update_transform(&mut self, transform: Transform);
+}
+
+/// Paints the same frames with several painters, like one per server.
+pub struct FanOut(pub Vec<Box<dyn FramePainter>>);
+
+impl FramePainter for FanOut {
+	fn update_frame(&mut self, frame: DynamicImage) {
+		for painter in &mut self.0 {
+			painter.update_frame(frame.clone());
+		}
+	}
+
+	fn update_dimensions(&mut self, dimensions: Dimension) {
+		for painter in &mut self.0 {
+			painter.update_dimensions(dimensions);
+		}
+	}
+
+	fn update_resize_type(&mut self, resize_type: ResizeType) {
+		for painter in &mut self.0 {
+			painter.update_resize_type(resize_type);
+		}
+	}
+
+	fn update_resize_filter(&mut self, resize_filter: FilterType) {
+		for painter in &mut self.0 {
+			painter.update_resize_filter(resize_filter);
+		}
+	}
+
+	fn update_position(&mut self, coordinate: Coordinate) {
+		for painter in &mut self.0 {
+			painter.update_position(coordinate);
+		}
+	}
+
+	fn update_stream_count(&mut self, count: usize) {
+		for painter in &mut self.0 {
+			painter.update_stream_count(count);
+		}
+	}
+
+	fn update_budget(&mut self, budget: Budget) {
+		for painter in &mut self.0 {
+			painter.update_budget(budget.clone());
+		}
+	}
+
+	fn update_serializer(&mut self, serializer: Box<dyn FrameSerializer + 'static>) {
+		for painter in &mut self.0 {
+			painter.update_serializer(serializer.duplicate());
+		}
+	}
+
+	fn update_quantizer(&mut self, quantizer: Option<Quantizer>) {
+		for painter in &mut self.0 {
+			painter.update_quantizer(quantizer.clone());
+		}
+	}
+
+	fn update_alpha_mode(&mut self, alpha_mode: AlphaMode) {
+		for painter in &mut self.0 {
+			painter.update_alpha_mode(alpha_mode);
+		}
+	}
+
+	fn update_transform(&mut self, transform: Transform) {
+		for painter in &mut self.0 {
+			painter.update_transform(transform);
+		}
+	}
 }
 
 pub struct FrameResizer {
 	original_frame: DynamicImage,
-	resized_frame: RgbaImage,
+	resized_frame: Arc<RgbaImage>,
 	dimensions: (u32, u32),
 	resize_type: ResizeType,
 	resize_filter: FilterType,
+	transform: Transform,
 }
 
 impl From<DynamicImage> for FrameResizer {
 	fn from(frame: DynamicImage) -> Self {
 		let dimensions = frame.dimensions();
-		let resized_frame = frame.to_rgba8();
+		let resized_frame = Arc::new(frame.to_rgba8());
 		Self {
 			original_frame: frame,
 			resized_frame,
 			dimensions,
 			resize_type: ResizeType::Stretch,
 			resize_filter: FilterType::Lanczos3,
+			transform: Transform::default(),
 		}
 	}
 }
 
 impl FrameResizer {
-	pub fn resized_frame(&self) -> RgbaImage {
-		self.resized_frame.clone()
+	pub fn resized_frame(&self) -> Arc<RgbaImage> {
+		Arc::clone(&self.resized_frame)
 	}
 
-	pub fn update_frame(&mut self, frame: DynamicImage) -> RgbaImage {
+	pub fn update_frame(&mut self, frame: DynamicImage) -> Arc<RgbaImage> {
 		self.original_frame = frame;
 		self.resize()
 	}
 
-	pub fn update_dimensions(&mut self, dimensions: Dimension) -> RgbaImage {
+	pub fn update_dimensions(&mut self, dimensions: Dimension) -> Arc<RgbaImage> {
 		self.dimensions = (
 			dimensions.width.try_into().unwrap(),
 			dimensions.height.try_into().unwrap(),
@@ -57,24 +140,35 @@ impl FrameResizer {
 		self.resize()
 	}
 
-	pub fn update_type(&mut self, resize_type: ResizeType) -> RgbaImage {
+	pub fn update_type(&mut self, resize_type: ResizeType) -> Arc<RgbaImage> {
 		self.resize_type = resize_type;
 		self.resize()
 	}
 
-	pub fn update_filter(&mut self, resize_filter: FilterType) -> RgbaImage {
+	pub fn update_filter(&mut self, resize_filter: FilterType) -> Arc<RgbaImage> {
 		self.resize_filter = resize_filter;
 		self.resize()
 	}
 
-	fn resize(&mut self) -> RgbaImage {
+	pub fn update_transform(&mut self, transform: Transform) -> Arc<RgbaImage> {
+		self.transform = transform;
+		self.resize()
+	}
+
+	fn resize(&mut self) -> Arc<RgbaImage> {
 		let (x, y) = self.dimensions;
-		self.resized_frame = resize_frame(self.original_frame.clone(), self.resize_type, self.resize_filter, x, y);
-		self.resized_frame.clone()
+		let resized = resize_frame(&self.original_frame, self.resize_type, self.resize_filter, x, y);
+		self.resized_frame = Arc::new(if self.transform.is_identity() {
+			resized
+		} else {
+			self.transform.apply(&resized)
+		});
+		self.resized_frame()
 	}
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ResizeType {
 	Crop,
 	Stretch,
@@ -87,13 +181,17 @@ impl Default for ResizeType {
 	}
 }
 
-fn resize_frame(
-	frame: DynamicImage,
+pub fn resize_frame(
+	frame: &DynamicImage,
 	resize_type: ResizeType,
 	resize_filter: FilterType,
 	width: u32,
 	height: u32,
 ) -> RgbaImage {
+	if frame.dimensions() == (width, height) {
+		return frame.to_rgba8();
+	}
+
 	let resized_image = match resize_type {
 		ResizeType::Crop => frame.resize_to_fill(width, height, resize_filter),
 		ResizeType::Stretch => frame.resize_exact(width, height, resize_filter),
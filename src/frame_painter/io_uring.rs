@@ -1,32 +1,53 @@
 use super::FrameResizer;
+use crate::address::Addresses;
+use crate::alpha::{AlphaMode, CanvasBackground};
+use crate::budget::Budget;
+use crate::canvas;
+use crate::frame_encoder::FrameEncoder;
 use crate::frame_painter::{FramePainter, ResizeType};
 use crate::frame_serializer::{FrameSerializer, RandomSerializer};
-use crate::{Coordinate, Dimension, Pixel};
-use anyhow::{anyhow, bail, Context};
+use crate::quantizer::Quantizer;
+use crate::shared_buffer::SharedBuffer;
+use crate::stats::Stats;
+use crate::transform::Transform;
+use crate::{Coordinate, Dimension};
+use anyhow::{anyhow, bail};
 use image::imageops::FilterType;
 use image::{DynamicImage, RgbaImage};
 use std::convert::Infallible;
-use std::io::Write;
-use std::net::SocketAddr;
+use std::sync::Arc;
 use std::thread;
 use tokio::sync::mpsc;
 use tokio::sync::mpsc::error::TryRecvError;
+use tokio::task;
 use tokio_uring::buf::BoundedBuf;
 use tokio_uring::net::TcpStream;
 
 pub struct IoUringFramePainter {
 	resizer: FrameResizer,
-	update_sender: mpsc::Sender<Update>,
+	update_sender: mpsc::UnboundedSender<Update>,
 	serializer: Box<dyn FrameSerializer + 'static>,
+	quantizer: Option<Arc<Quantizer>>,
+	alpha_mode: AlphaMode,
 	stream_count: usize,
+	budget: Arc<Budget>,
 	position: Coordinate,
 }
 
 impl IoUringFramePainter {
-	pub fn start(socket_address: SocketAddr, frame: DynamicImage) -> IoUringFramePainter {
-		let (update_sender, update_receiver) = mpsc::channel(2);
+	pub fn start(addresses: Addresses, frame: DynamicImage, stats: Arc<Stats>) -> IoUringFramePainter {
+		// unbounded, so that the latest update is never dropped, the encoder skips the superseded ones
+		let (update_sender, update_receiver) = mpsc::unbounded_channel();
+		let (buffer_sender, buffer_receiver) = mpsc::channel(1);
+		let encoder_stats = Arc::clone(&stats);
+		let encoder_addresses = addresses.clone();
 		thread::spawn(move || {
-			if let Err(error) = tokio_uring::start(run_io(socket_address, update_receiver)) {
+			if let Err(error) = run_encoder(&encoder_addresses, update_receiver, buffer_sender, &encoder_stats) {
+				println!("Frame encoder failed: {error}");
+			}
+		});
+		thread::spawn(move || {
+			if let Err(error) = tokio_uring::start(run_io(addresses, buffer_receiver, stats)) {
 				println!("Framepainter failed: {error}");
 			}
 		});
@@ -34,7 +55,10 @@ impl IoUringFramePainter {
 			resizer: FrameResizer::from(frame),
 			update_sender,
 			serializer: Box::new(RandomSerializer::default()),
+			quantizer: None,
+			alpha_mode: AlphaMode::default(),
 			stream_count: 0,
+			budget: Arc::new(Budget::default()),
 			position: Coordinate::default(),
 		}
 	}
@@ -43,97 +67,161 @@ impl IoUringFramePainter {
 impl FramePainter for IoUringFramePainter {
 	fn update_frame(&mut self, frame: DynamicImage) {
 		let frame = self.resizer.update_frame(frame);
-		let _ = self.update_sender.try_send(Update {
-			frame,
-			serializer: self.serializer.duplicate(),
-			stream_count: self.stream_count,
-			position: self.position,
-		});
+		self.send_update(frame);
 	}
 
 	fn update_dimensions(&mut self, dimensions: Dimension) {
 		let frame = self.resizer.update_dimensions(dimensions);
-		let _ = self.update_sender.try_send(Update {
-			frame,
-			serializer: self.serializer.duplicate(),
-			stream_count: self.stream_count,
-			position: self.position,
-		});
+		self.send_update(frame);
 	}
 
 	fn update_resize_type(&mut self, resize_type: ResizeType) {
 		let frame = self.resizer.update_type(resize_type);
-		let _ = self.update_sender.try_send(Update {
-			frame,
-			serializer: self.serializer.duplicate(),
-			stream_count: self.stream_count,
-			position: self.position,
-		});
+		self.send_update(frame);
 	}
 
 	fn update_resize_filter(&mut self, resize_filter: FilterType) {
 		let frame = self.resizer.update_filter(resize_filter);
-		let _ = self.update_sender.try_send(Update {
-			frame,
-			serializer: self.serializer.duplicate(),
-			stream_count: self.stream_count,
-			position: self.position,
-		});
+		self.send_update(frame);
 	}
 
 	fn update_position(&mut self, position: Coordinate) {
-		let frame = self.resizer.resized_frame();
 		self.position = position;
-		let _ = self.update_sender.try_send(Update {
-			frame,
-			serializer: self.serializer.duplicate(),
-			stream_count: self.stream_count,
-			position: self.position,
-		});
+		self.send_update(self.resizer.resized_frame());
 	}
 
 	fn update_stream_count(&mut self, count: usize) {
-		let frame = self.resizer.resized_frame();
 		self.stream_count = count;
-		let _ = self.update_sender.try_send(Update {
-			frame,
-			serializer: self.serializer.duplicate(),
-			stream_count: self.stream_count,
-			position: self.position,
-		});
+		self.send_update(self.resizer.resized_frame());
+	}
+
+	fn update_budget(&mut self, budget: Budget) {
+		self.budget = Arc::new(budget);
+		self.send_update(self.resizer.resized_frame());
 	}
 
 	fn update_serializer(&mut self, serializer: Box<dyn FrameSerializer + 'static>) {
-		let frame = self.resizer.resized_frame();
 		self.serializer = serializer;
-		let _ = self.update_sender.try_send(Update {
+		self.send_update(self.resizer.resized_frame());
+	}
+
+	fn update_quantizer(&mut self, quantizer: Option<Quantizer>) {
+		self.quantizer = quantizer.map(Arc::new);
+		self.send_update(self.resizer.resized_frame());
+	}
+
+	fn update_alpha_mode(&mut self, alpha_mode: AlphaMode) {
+		self.alpha_mode = alpha_mode;
+		self.send_update(self.resizer.resized_frame());
+	}
+
+	fn update_transform(&mut self, transform: Transform) {
+		let frame = self.resizer.update_transform(transform);
+		self.send_update(frame);
+	}
+}
+
+impl IoUringFramePainter {
+	fn send_update(&self, frame: Arc<RgbaImage>) {
+		let _ = self.update_sender.send(Update {
 			frame,
 			serializer: self.serializer.duplicate(),
+			quantizer: self.quantizer.clone(),
+			alpha_mode: self.alpha_mode,
 			stream_count: self.stream_count,
+			budget: Arc::clone(&self.budget),
 			position: self.position,
 		});
 	}
 }
 
 struct Update {
-	frame: RgbaImage,
+	frame: Arc<RgbaImage>,
 	serializer: Box<dyn FrameSerializer + 'static>,
+	quantizer: Option<Arc<Quantizer>>,
+	alpha_mode: AlphaMode,
 	stream_count: usize,
+	budget: Arc<Budget>,
 	position: Coordinate,
 }
 
-async fn run_io(socket_address: SocketAddr, mut update_receiver: mpsc::Receiver<Update>) -> anyhow::Result<Infallible> {
-	let mut senders = Vec::<mpsc::Sender<Vec<u8>>>::new();
+/// Preprocesses, serializes and encodes updates off the io_uring thread, so that it only receives finished buffers.
+fn run_encoder(
+	addresses: &Addresses,
+	mut update_receiver: mpsc::UnboundedReceiver<Update>,
+	buffer_sender: mpsc::Sender<Vec<SharedBuffer>>,
+	stats: &Stats,
+) -> anyhow::Result<Infallible> {
+	let canvas_size = match canvas::read_size(addresses) {
+		Ok(size) => Some(size),
+		Err(error) => {
+			println!("Couldn't read the canvas size, only clipping negative coordinates: {error}");
+			None
+		}
+	};
+	let mut encoder = FrameEncoder::default();
+	let mut background = CanvasBackground::default();
 	loop {
+		let mut update = update_receiver
+			.blocking_recv()
+			.ok_or_else(|| anyhow!("Update channel closed"))?;
+		// skip updates that have already been superseded
+		while let Ok(newer_update) = update_receiver.try_recv() {
+			update = newer_update;
+		}
+
 		let Update {
 			frame,
 			mut serializer,
+			quantizer,
+			alpha_mode,
 			stream_count,
+			budget,
 			position,
-		} = update_receiver
+		} = update;
+		let frame = match alpha_mode {
+			AlphaMode::Server => frame,
+			AlphaMode::Composite => {
+				if !background.covers(position, &frame) {
+					background = CanvasBackground::new(position, frame.width(), frame.height());
+				}
+				if let Err(error) = background.update(addresses, &frame, canvas_size) {
+					println!("Reading the canvas failed, sending frames as is until they move: {error}");
+				}
+				Arc::new(background.composite(&frame))
+			}
+		};
+		let frame = match quantizer {
+			Some(quantizer) => Arc::new(quantizer.quantize(&frame)),
+			None => frame,
+		};
+		let buffers = encoder.encode(
+			&frame,
+			serializer.as_mut(),
+			&budget,
+			stream_count,
+			position,
+			canvas_size,
+		);
+		stats.add_frame();
+		buffer_sender
+			.blocking_send(buffers)
+			.map_err(|_| anyhow!("Buffer channel closed"))?;
+	}
+}
+
+async fn run_io(
+	addresses: Addresses,
+	mut buffer_receiver: mpsc::Receiver<Vec<SharedBuffer>>,
+	stats: Arc<Stats>,
+) -> anyhow::Result<Infallible> {
+	let mut senders = Vec::<mpsc::Sender<SharedBuffer>>::new();
+	loop {
+		let buffers = buffer_receiver
 			.recv()
 			.await
-			.ok_or_else(|| anyhow!("Update channel closed"))?;
+			.ok_or_else(|| anyhow!("Buffer channel closed"))?;
+		let stream_count = buffers.len();
 		if stream_count == 0 {
 			senders.clear();
 			continue;
@@ -145,46 +233,33 @@ async fn run_io(socket_address: SocketAddr, mut update_receiver: mpsc::Receiver<
 			}
 			length if length < stream_count => {
 				// start missing streams
-				for _ in senders.len()..stream_count {
+				for index in senders.len()..stream_count {
 					let (sender, receiver) = mpsc::channel(1);
 					senders.push(sender);
-					let stream = match TcpStream::connect(socket_address).await {
-						Ok(stream) => stream,
+					let addresses = addresses.for_connection(index);
+					// happy eyeballs uses blocking connects on several threads
+					let stream = match task::spawn_blocking(move || addresses.connect()).await? {
+						Ok(stream) => TcpStream::from_std(stream),
 						Err(error) => {
 							println!("Connection failed, retrying on next update: {error}");
+							stats.connection_failed();
 							continue;
 						}
 					};
 					println!("Connected");
+					stats.connected();
+					let stats = Arc::clone(&stats);
 					tokio_uring::spawn(async move {
-						if let Err(error) = run_single_stream(stream, receiver).await {
+						if let Err(error) = run_single_stream(stream, receiver, &stats).await {
 							println!("Stream task failed with: {error}");
 						}
+						stats.disconnected();
 					});
 				}
 			}
 			_ => {}
 		}
 
-		let mut pixels = serializer.serialize(&frame);
-		let pixel_count = frame.pixels().len();
-		let pixels_per_stream = pixel_count / stream_count;
-		let mut buffers = vec![Vec::<u8>::with_capacity(Pixel::BYTE_ESTIMATE); stream_count];
-		for buffer in &mut buffers {
-			for _ in 0..pixels_per_stream {
-				let mut pixel = pixels.next().context("Pixel count mismatch")?;
-				pixel.coordinate += position;
-				buffer.write_fmt(format_args!("{pixel}"))?;
-			}
-		}
-
-		// write remaining pixels
-		if let Some(last_buffer) = buffers.last_mut() {
-			for mut pixel in pixels {
-				pixel.coordinate += position;
-				last_buffer.write_fmt(format_args!("{pixel}"))?;
-			}
-		}
 		for (sender, buffer) in senders.iter().zip(buffers) {
 			if sender.send(buffer).await.is_err() {
 				println!("Broken stream.");
@@ -193,7 +268,11 @@ async fn run_io(socket_address: SocketAddr, mut update_receiver: mpsc::Receiver<
 	}
 }
 
-async fn run_single_stream(stream: TcpStream, mut receiver: mpsc::Receiver<Vec<u8>>) -> anyhow::Result<Infallible> {
+async fn run_single_stream(
+	stream: TcpStream,
+	mut receiver: mpsc::Receiver<SharedBuffer>,
+	stats: &Stats,
+) -> anyhow::Result<Infallible> {
 	let mut buffer = receiver
 		.recv()
 		.await
@@ -211,11 +290,20 @@ async fn run_single_stream(stream: TcpStream, mut receiver: mpsc::Receiver<Vec<u
 				}
 				Err(TryRecvError::Disconnected) => bail!("Stream stopped."),
 			}
+			if buffer.begin() == buffer.end() {
+				// Nothing to paint, wait for the next frame instead of writing nothing over and over
+				buffer = receiver
+					.recv()
+					.await
+					.ok_or_else(|| anyhow!("Stream stopped."))?
+					.slice(..);
+				continue;
+			}
 		}
 
 		buffer = {
 			let (result, buffer) = stream.write(buffer).submit().await;
-			result?;
+			stats.add_bytes(result?);
 			buffer
 		};
 	}
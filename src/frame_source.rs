@@ -0,0 +1,84 @@
+use crate::frame_painter::FramePainter;
+use anyhow::{bail, Context};
+use image::DynamicImage;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Produces the frames that are fed into `FramePainter::update_frame`.
+pub trait FrameSource: Send {
+	/// Returns the next frame or `None` if the content hasn't changed since the last call.
+	fn next_frame(&mut self) -> anyhow::Result<Option<DynamicImage>>;
+
+	/// Time to wait between two calls to `next_frame`, `None` if the content never changes.
+	fn interval(&self) -> Option<Duration>;
+}
+
+/// Time between two frames at `rate` frames per second.
+pub fn frame_interval(rate: f64) -> anyhow::Result<Duration> {
+	if !(rate.is_finite() && rate > 0.0) {
+		bail!("Invalid rate {rate}, expected a positive number of frames per second");
+	}
+	Duration::try_from_secs_f64(1.0 / rate).with_context(|| format!("Rate {rate} is too low"))
+}
+
+/// Frame source for content that never changes, like a single image.
+pub struct StaticFrame(Option<DynamicImage>);
+
+impl From<DynamicImage> for StaticFrame {
+	fn from(frame: DynamicImage) -> Self {
+		Self(Some(frame))
+	}
+}
+
+impl FrameSource for StaticFrame {
+	fn next_frame(&mut self) -> anyhow::Result<Option<DynamicImage>> {
+		Ok(self.0.take())
+	}
+
+	fn interval(&self) -> Option<Duration> {
+		None
+	}
+}
+
+/// Frame source together with the painter its frames are fed into.
+pub struct Pipeline {
+	pub source: Box<dyn FrameSource>,
+	pub painter: Box<dyn FramePainter>,
+}
+
+/// Runs every pipeline on its own thread, so that a slow source doesn't hold back the others.
+/// Never returns unless a source fails.
+pub fn run_frame_sources(pipelines: Vec<Pipeline>) -> anyhow::Result<()> {
+	let (result_sender, result_receiver) = mpsc::channel();
+	for pipeline in pipelines {
+		let result_sender = result_sender.clone();
+		thread::spawn(move || {
+			// The receiver is gone if another pipeline failed first
+			let _ = result_sender.send(run_frame_source(pipeline));
+		});
+	}
+	drop(result_sender);
+	result_receiver.recv().unwrap_or(Ok(()))
+}
+
+/// Feeds every new frame of the source to the painter, never returns unless the source fails.
+fn run_frame_source(
+	Pipeline {
+		mut source,
+		mut painter,
+	}: Pipeline,
+) -> anyhow::Result<()> {
+	loop {
+		match source.interval() {
+			Some(interval) => {
+				let start = Instant::now();
+				if let Some(frame) = source.next_frame()? {
+					painter.update_frame(frame);
+				}
+				thread::sleep(interval.saturating_sub(start.elapsed()));
+			}
+			None => thread::sleep(Duration::from_secs(10)),
+		}
+	}
+}
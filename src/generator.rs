@@ -0,0 +1,44 @@
+use crate::frame_source::{frame_interval, FrameSource};
+use anyhow::Context;
+use image::{DynamicImage, RgbaImage};
+use std::time::Duration;
+
+pub mod fire;
+pub mod life;
+pub mod noise;
+pub mod plasma;
+pub mod starfield;
+
+/// Procedural animation that draws a new frame on every tick.
+pub trait Generator: Send {
+	/// Advances the animation by one tick and draws the result into `frame`.
+	fn step(&mut self, frame: &mut RgbaImage);
+}
+
+/// Runs a `Generator` at a fixed tick rate.
+pub struct Animated<G> {
+	generator: G,
+	frame: RgbaImage,
+	interval: Duration,
+}
+
+impl<G: Generator> Animated<G> {
+	pub fn new(generator: G, width: u32, height: u32, tick_rate: f64) -> anyhow::Result<Self> {
+		Ok(Self {
+			generator,
+			frame: RgbaImage::new(width, height),
+			interval: frame_interval(tick_rate).context("Invalid generator tick_rate")?,
+		})
+	}
+}
+
+impl<G: Generator> FrameSource for Animated<G> {
+	fn next_frame(&mut self) -> anyhow::Result<Option<DynamicImage>> {
+		self.generator.step(&mut self.frame);
+		Ok(Some(DynamicImage::ImageRgba8(self.frame.clone())))
+	}
+
+	fn interval(&self) -> Option<Duration> {
+		Some(self.interval)
+	}
+}
@@ -0,0 +1,107 @@
+use super::Generator;
+use crate::settings;
+use anyhow::bail;
+use image::{Rgba, RgbaImage};
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+
+/// Palette of the PSX Doom fire effect, from cold to hot.
+const PALETTE: [[u8; 3]; 37] = [
+	[0x07, 0x07, 0x07],
+	[0x1f, 0x07, 0x07],
+	[0x2f, 0x0f, 0x07],
+	[0x47, 0x0f, 0x07],
+	[0x57, 0x17, 0x07],
+	[0x67, 0x1f, 0x07],
+	[0x77, 0x1f, 0x07],
+	[0x8f, 0x27, 0x07],
+	[0x9f, 0x2f, 0x07],
+	[0xaf, 0x3f, 0x07],
+	[0xbf, 0x47, 0x07],
+	[0xc7, 0x47, 0x07],
+	[0xdf, 0x4f, 0x07],
+	[0xdf, 0x57, 0x07],
+	[0xdf, 0x57, 0x07],
+	[0xd7, 0x5f, 0x07],
+	[0xd7, 0x5f, 0x07],
+	[0xd7, 0x67, 0x0f],
+	[0xcf, 0x6f, 0x0f],
+	[0xcf, 0x77, 0x0f],
+	[0xcf, 0x7f, 0x0f],
+	[0xcf, 0x87, 0x17],
+	[0xc7, 0x87, 0x17],
+	[0xc7, 0x8f, 0x17],
+	[0xc7, 0x97, 0x1f],
+	[0xbf, 0x9f, 0x1f],
+	[0xbf, 0x9f, 0x1f],
+	[0xbf, 0xa7, 0x27],
+	[0xbf, 0xa7, 0x27],
+	[0xbf, 0xaf, 0x2f],
+	[0xb7, 0xaf, 0x2f],
+	[0xb7, 0xb7, 0x2f],
+	[0xb7, 0xb7, 0x37],
+	[0xcf, 0xcf, 0x6f],
+	[0xdf, 0xdf, 0x9f],
+	[0xef, 0xef, 0xc7],
+	[0xff, 0xff, 0xff],
+];
+
+/// The fire effect from the PSX version of Doom: heat rises from the bottom row and cools down randomly.
+pub struct Fire {
+	/// Heat of every pixel as index into the palette
+	heat: Vec<u8>,
+	width: usize,
+	height: usize,
+	decay: u8,
+	wind: i64,
+	rng: SmallRng,
+}
+
+impl Fire {
+	pub fn new(settings: &settings::Fire, width: u32, height: u32) -> anyhow::Result<Self> {
+		let (width, height) = (width as usize, height as usize);
+		// the heat can't drift further than once around the frame
+		if settings.wind.unsigned_abs() >= width.max(1) as u64 {
+			bail!(
+				"Fire wind has to be smaller than the width {}, got {}",
+				width,
+				settings.wind
+			);
+		}
+		let mut heat = vec![0; width * height];
+		// the bottom row is the source of the fire
+		if let Some(bottom) = heat.chunks_mut(width.max(1)).last() {
+			bottom.fill((PALETTE.len() - 1) as u8);
+		}
+
+		Ok(Self {
+			heat,
+			width,
+			height,
+			decay: settings.decay,
+			wind: settings.wind,
+			rng: SmallRng::from_entropy(),
+		})
+	}
+}
+
+impl Generator for Fire {
+	fn step(&mut self, frame: &mut RgbaImage) {
+		for y in 1..self.height {
+			for x in 0..self.width {
+				let source = self.heat[y * self.width + x];
+				let decay = self.rng.gen_range(0..=self.decay);
+				let drift = self.rng.gen_range(0..=self.wind.abs()) * self.wind.signum();
+				let target_x = (x as i64 + drift).rem_euclid(self.width as i64) as usize;
+				self.heat[(y - 1) * self.width + target_x] = source.saturating_sub(decay);
+			}
+		}
+
+		for (pixel, heat) in frame.pixels_mut().zip(&self.heat) {
+			let [red, green, blue] = PALETTE[usize::from(*heat)];
+			// the coldest color is transparent, so the canvas shows through above the flames
+			let alpha = if *heat == 0 { 0 } else { u8::MAX };
+			*pixel = Rgba([red, green, blue, alpha]);
+		}
+	}
+}
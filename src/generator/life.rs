@@ -0,0 +1,127 @@
+use super::Generator;
+use crate::address::Addresses;
+use crate::canvas;
+use crate::pixel::Color;
+use crate::settings;
+use crate::Coordinate;
+use anyhow::bail;
+use image::{Rgba, RgbaImage};
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+
+/// Conway's Game of Life on a torus, reseeded randomly once it gets stuck.
+pub struct Life {
+	cells: Vec<bool>,
+	next_cells: Vec<bool>,
+	/// Generation before the current one, to detect blinkers that alternate between two states
+	previous_cells: Vec<bool>,
+	width: usize,
+	height: usize,
+	density: f64,
+	alive: Rgba<u8>,
+	dead: Rgba<u8>,
+	rng: SmallRng,
+}
+
+impl Life {
+	pub fn new(settings: &settings::Life, width: u32, height: u32) -> anyhow::Result<Self> {
+		if !(0.0..=1.0).contains(&settings.density) {
+			bail!("Life density has to be between 0.0 and 1.0, got {}", settings.density);
+		}
+		let (width, height) = (width as usize, height as usize);
+		let mut life = Self {
+			cells: vec![false; width * height],
+			next_cells: vec![false; width * height],
+			previous_cells: vec![false; width * height],
+			width,
+			height,
+			density: settings.density,
+			alive: settings.alive.into(),
+			dead: settings.dead.map(Rgba::from).unwrap_or(Rgba([0, 0, 0, 0])),
+			rng: SmallRng::from_entropy(),
+		};
+		life.seed_randomly();
+		Ok(life)
+	}
+
+	/// Uses the current canvas content as first generation, bright pixels are alive.
+	pub fn seed_from_canvas(
+		&mut self,
+		addresses: &Addresses,
+		position: Coordinate,
+		threshold: u8,
+	) -> anyhow::Result<()> {
+		let canvas_size = match canvas::read_size(addresses) {
+			Ok(size) => Some(size),
+			Err(error) => {
+				println!("Couldn't read the canvas size, only seeding from positive coordinates: {error}");
+				None
+			}
+		};
+		// cells outside of the canvas can't be read and start dead
+		let width = self.width;
+		let (cells, coordinates): (Vec<_>, Vec<_>) = (0..self.height)
+			.flat_map(|y| (0..width).map(move |x| Coordinate::new(x as u32, y as u32) + position))
+			.enumerate()
+			.filter(|&(_, coordinate)| canvas::is_visible(coordinate, canvas_size))
+			.unzip();
+		let colors = canvas::read_pixels(addresses, &coordinates)?;
+		self.cells.fill(false);
+		for (cell, color) in cells.into_iter().zip(colors) {
+			self.cells[cell] = brightness(color) >= threshold;
+		}
+		Ok(())
+	}
+
+	fn seed_randomly(&mut self) {
+		for cell in &mut self.cells {
+			*cell = self.rng.gen_bool(self.density);
+		}
+	}
+
+	fn alive_neighbours(&self, x: usize, y: usize) -> usize {
+		let mut count = 0;
+		for dy in [self.height - 1, 0, 1] {
+			for dx in [self.width - 1, 0, 1] {
+				if (dx, dy) == (0, 0) {
+					continue;
+				}
+				let neighbour_x = (x + dx) % self.width;
+				let neighbour_y = (y + dy) % self.height;
+				count += usize::from(self.cells[neighbour_y * self.width + neighbour_x]);
+			}
+		}
+		count
+	}
+}
+
+impl Generator for Life {
+	fn step(&mut self, frame: &mut RgbaImage) {
+		for (pixel, alive) in frame.pixels_mut().zip(&self.cells) {
+			*pixel = if *alive { self.alive } else { self.dead };
+		}
+
+		if self.width == 0 || self.height == 0 {
+			return;
+		}
+		for y in 0..self.height {
+			for x in 0..self.width {
+				let alive = self.cells[y * self.width + x];
+				self.next_cells[y * self.width + x] =
+					matches!((alive, self.alive_neighbours(x, y)), (true, 2) | (_, 3));
+			}
+		}
+
+		let stuck = self.next_cells == self.cells || self.next_cells == self.previous_cells;
+		std::mem::swap(&mut self.previous_cells, &mut self.cells);
+		std::mem::swap(&mut self.cells, &mut self.next_cells);
+		if stuck {
+			self.seed_randomly();
+		}
+	}
+}
+
+fn brightness(color: Color) -> u8 {
+	let sum = u16::from(color.red()) + u16::from(color.green()) + u16::from(color.blue());
+	(sum / 3) as u8
+}
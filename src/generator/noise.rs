@@ -0,0 +1,33 @@
+use super::Generator;
+use crate::settings;
+use image::{Rgba, RgbaImage};
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+
+/// Random static like an untuned TV.
+pub struct Noise {
+	grayscale: bool,
+	rng: SmallRng,
+}
+
+impl Noise {
+	pub fn new(settings: &settings::Noise) -> Self {
+		Self {
+			grayscale: settings.grayscale,
+			rng: SmallRng::from_entropy(),
+		}
+	}
+}
+
+impl Generator for Noise {
+	fn step(&mut self, frame: &mut RgbaImage) {
+		for pixel in frame.pixels_mut() {
+			*pixel = if self.grayscale {
+				let gray = self.rng.gen();
+				Rgba([gray, gray, gray, u8::MAX])
+			} else {
+				Rgba([self.rng.gen(), self.rng.gen(), self.rng.gen(), u8::MAX])
+			};
+		}
+	}
+}
@@ -0,0 +1,40 @@
+use super::Generator;
+use crate::settings;
+use image::{Rgba, RgbaImage};
+use std::f64::consts::PI;
+
+/// Classic demoscene plasma made of overlapping sine waves.
+pub struct Plasma {
+	scale: f64,
+	speed: f64,
+	time: f64,
+}
+
+impl Plasma {
+	pub fn new(settings: &settings::Plasma) -> Self {
+		Self {
+			scale: settings.scale,
+			speed: settings.speed,
+			time: 0.0,
+		}
+	}
+}
+
+impl Generator for Plasma {
+	fn step(&mut self, frame: &mut RgbaImage) {
+		self.time += self.speed;
+		let time = self.time;
+		let (center_x, center_y) = (f64::from(frame.width()) / 2.0, f64::from(frame.height()) / 2.0);
+		for (x, y, pixel) in frame.enumerate_pixels_mut() {
+			let x = f64::from(x) * self.scale;
+			let y = f64::from(y) * self.scale;
+			let distance = ((x - center_x * self.scale).powi(2) + (y - center_y * self.scale).powi(2)).sqrt();
+			let value =
+				(x + time).sin() + ((y + time) / 2.0).sin() + ((x + y + time) / 2.0).sin() + (distance + time).sin();
+			// value is in -4..4, map it to one full turn of the color wheel
+			let phase = value * PI / 4.0;
+			let channel = |offset: f64| ((phase + offset).sin() * 127.5 + 127.5) as u8;
+			*pixel = Rgba([channel(0.0), channel(2.0 * PI / 3.0), channel(4.0 * PI / 3.0), u8::MAX]);
+		}
+	}
+}
@@ -0,0 +1,77 @@
+use super::Generator;
+use crate::settings;
+use anyhow::bail;
+use image::{Rgba, RgbaImage};
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+
+const MAXIMUM_DEPTH: f64 = 1.0;
+const MINIMUM_DEPTH: f64 = 0.01;
+
+/// Stars flying towards the viewer.
+pub struct Starfield {
+	stars: Vec<Star>,
+	speed: f64,
+	rng: SmallRng,
+}
+
+struct Star {
+	x: f64,
+	y: f64,
+	depth: f64,
+}
+
+impl Starfield {
+	pub fn new(settings: &settings::Starfield) -> anyhow::Result<Self> {
+		if !(settings.speed.is_finite() && settings.speed >= 0.0) {
+			bail!("Invalid starfield speed {}", settings.speed);
+		}
+		let mut rng = SmallRng::from_entropy();
+		let stars = (0..settings.stars)
+			.map(|_| {
+				let mut star = Star::random(&mut rng);
+				star.depth = rng.gen_range(MINIMUM_DEPTH..MAXIMUM_DEPTH);
+				star
+			})
+			.collect();
+		Ok(Self {
+			stars,
+			speed: settings.speed,
+			rng,
+		})
+	}
+}
+
+impl Star {
+	fn random(rng: &mut SmallRng) -> Self {
+		Self {
+			x: rng.gen_range(-1.0..1.0),
+			y: rng.gen_range(-1.0..1.0),
+			depth: MAXIMUM_DEPTH,
+		}
+	}
+}
+
+impl Generator for Starfield {
+	fn step(&mut self, frame: &mut RgbaImage) {
+		for pixel in frame.pixels_mut() {
+			*pixel = Rgba([0, 0, 0, u8::MAX]);
+		}
+
+		let (half_width, half_height) = (f64::from(frame.width()) / 2.0, f64::from(frame.height()) / 2.0);
+		for star in &mut self.stars {
+			star.depth -= self.speed;
+			let x = half_width + star.x / star.depth * half_width;
+			let y = half_height + star.y / star.depth * half_height;
+			let visible = (0.0..f64::from(frame.width())).contains(&x) && (0.0..f64::from(frame.height())).contains(&y);
+			if star.depth <= MINIMUM_DEPTH || !visible {
+				*star = Star::random(&mut self.rng);
+				continue;
+			}
+
+			// stars get brighter the closer they are
+			let brightness = ((1.0 - star.depth / MAXIMUM_DEPTH) * 255.0) as u8;
+			frame.put_pixel(x as u32, y as u32, Rgba([brightness, brightness, brightness, u8::MAX]));
+		}
+	}
+}
@@ -16,8 +16,8 @@ pub fn image_to_field(dimension: Dimension, offset: Coordinate, filename: &str)
 			let pixel = rgb.get_pixel(x as u32, y as u32);
 			field[x][y] = Pixel {
 				coordinate: Coordinate {
-					x: x + offset.x,
-					y: y + offset.y,
+					x: x as i64 + offset.x,
+					y: y as i64 + offset.y,
 				},
 				color: Color::rgb(pixel[0], pixel[1], pixel[2]),
 			}
@@ -1,89 +1,384 @@
 extern crate rand;
-use std::net::ToSocketAddrs;
-use std::thread;
+use std::convert::TryInto;
+use std::env;
+use std::sync::Arc;
 use std::time::Duration;
+use std::{iter, mem};
 
+mod address;
+mod alpha;
+mod arguments;
+mod benchmark;
+mod budget;
+mod canvas;
 mod complex;
+mod compositor;
 mod coordinate;
+mod double_double;
 mod fractal;
+mod frame_encoder;
 mod frame_painter;
 mod frame_serializer;
+mod frame_source;
+mod generator;
 mod images;
 mod pixel;
 mod pixel_backend;
+mod pixel_encoder;
+mod playlist;
+mod quantizer;
+mod raw_stdin;
 mod settings;
+mod shared_buffer;
+mod stats;
+mod text;
+mod transform;
+mod video;
+mod widget;
 
+use crate::address::Addresses;
+use crate::arguments::Mode;
+use crate::budget::{Budget, Region};
+use crate::compositor::{Compositor, Layer};
+use crate::fractal::animation::Animation;
+use crate::fractal::{FractalType, View};
 use crate::frame_painter::io_uring::IoUringFramePainter;
-use crate::frame_painter::FramePainter;
-use crate::settings::{Settings, Style};
-use anyhow::Context;
+use crate::frame_painter::{FanOut, FramePainter, ResizeType};
+use crate::frame_source::{run_frame_sources, FrameSource, Pipeline, StaticFrame};
+use crate::generator::fire::Fire;
+use crate::generator::life::Life;
+use crate::generator::noise::Noise;
+use crate::generator::plasma::Plasma;
+use crate::generator::starfield::Starfield;
+use crate::generator::Animated;
+use crate::playlist::Playlist;
+use crate::raw_stdin::RawStdin;
+use crate::settings::{Clock, Content, Settings, Style, Target};
+use crate::stats::Stats;
+use crate::text::TextRenderer;
+use crate::video::Video;
+use crate::widget::{AnalogClock, TextWidget};
+use anyhow::{bail, Context};
 use coordinate::Coordinate;
 use coordinate::Dimension;
+use image::imageops::FilterType;
+use image::DynamicImage;
 use pixel::Pixel;
 
 fn main() -> anyhow::Result<()> {
-	let settings = Settings::new()
+	let mode = Mode::from_arguments(env::args().skip(1))?;
+	if let Mode::BenchmarkEncoder = mode {
+		benchmark::run_encoder_benchmark();
+		return Ok(());
+	}
+
+	let mut settings = Settings::new()
 		.map_err(|error| eprintln!("Failed to read config with error: {}", error))
 		.unwrap();
 
-	let image = match settings.style {
-		Style::Julia | Style::Mandelbrot => {
-			/*
-			let mut field = Field::new(settings.dimension);
+	let addresses = Addresses::resolve(&settings.host, settings.port, settings.prefer_ipv6)?
+		.with_sources(settings.source_addresses.clone());
+	let mut frame_source = frame_source(mode, &mut settings, &addresses)?;
+	let frame = frame_source
+		.next_frame()?
+		.context("Frame source didn't produce an initial frame.")?;
 
-			for (x, y) in field.coordinates_iterator() {
-				let fractal_width = 4.0;
-				let fractal_height =
-					(settings.dimension.height as f64 / settings.dimension.width as f64) * fractal_width;
-				let fractal_x_offset = 0.0;
-				let fractal_y_offset = 0.0;
-				let c = Complex {
-					real: (x as f64 / settings.dimension.width as f64) * fractal_width - fractal_width / 2.0
-						+ fractal_x_offset,
-					imag: (y as f64 / settings.dimension.height as f64) * fractal_height - fractal_height / 2.0
-						+ fractal_y_offset,
+	let main_target = Target {
+		name: None,
+		host: settings.host.clone(),
+		port: settings.port,
+		connections: settings.connections,
+		offset: settings.offset,
+		dimension: settings.dimension,
+		content: None,
+	};
+	// The main target reuses the addresses its content was created with
+	let other_targets = mem::take(&mut settings.targets)
+		.into_iter()
+		.map(|target| (target, None));
+	let targets = iter::once((main_target, Some(addresses))).chain(other_targets);
+	let mut shared_painters = Vec::<Box<dyn FramePainter>>::new();
+	let mut pipelines = Vec::new();
+	let mut all_stats = Vec::new();
+	for (mut target, addresses) in targets {
+		let addresses = match addresses {
+			Some(addresses) => addresses,
+			None => Addresses::resolve(&target.host, target.port, settings.prefer_ipv6)?
+				.with_sources(settings.source_addresses.clone()),
+		};
+		println!("{}:{} resolved to {}", target.host, target.port, addresses);
+		let name = target
+			.name
+			.clone()
+			.unwrap_or_else(|| format!("{}:{}", target.host, target.port));
+		let stats = Arc::new(Stats::new(name.clone()));
+		all_stats.push(Arc::clone(&stats));
+		match target.content.take() {
+			None => {
+				// The regions of the layers only fit frames that aren't resized or transformed
+				let budget = match target.dimension == settings.dimension && settings.transform.is_identity() {
+					true => budget(&settings)?,
+					false => Budget::default(),
 				};
+				let painter = start_painter(&settings, &target, addresses, frame.clone(), budget, stats);
+				shared_painters.push(Box::new(painter));
+			}
+			Some(mut content) => {
+				let mut source = content_source(
+					&mut content,
+					target.dimension,
+					target.offset,
+					settings.resize_type,
+					settings.resize_filter.into(),
+					&addresses,
+				)
+				.with_context(|| format!("Failed to create the content of {}.", name))?;
+				let frame = source
+					.next_frame()?
+					.with_context(|| format!("Content of {} didn't produce an initial frame.", name))?;
+				let painter = start_painter(&settings, &target, addresses, frame, Budget::default(), stats);
+				pipelines.push(Pipeline {
+					source,
+					painter: Box::new(painter),
+				});
+			}
+		}
+	}
+	pipelines.insert(
+		0,
+		Pipeline {
+			source: frame_source,
+			painter: Box::new(FanOut(shared_painters)),
+		},
+	);
 
-				let iteration_factor = match settings.style {
-					Style::Julia => julia(c, settings.fractal.initial_value, settings.fractal.iterations),
-					Style::Mandelbrot => mandelbrot(c, settings.fractal.iterations),
-					_ => panic!("Not a fractal!"),
-				};
-				let color = if iteration_factor < settings.fractal.active_threshold {
-					Color::null()
-				} else {
-					Color::gradient24(iteration_factor)
-				};
+	if settings.stats_interval > 0.0 {
+		let interval = Duration::try_from_secs_f64(settings.stats_interval)
+			.with_context(|| format!("Invalid stats interval {}", settings.stats_interval))?;
+		stats::spawn_reporter(all_stats, interval);
+	}
+	run_frame_sources(pipelines)
+}
 
-				field[x][y] = Pixel {
-					coordinate: Coordinate {
-						x: x + settings.offset.x,
-						y: y + settings.offset.y,
-					},
-					color,
-				};
-			}
-			field
+/// Starts painting `frame` on the server of `target` with the painter settings of the top level.
+fn start_painter(
+	settings: &Settings,
+	target: &Target,
+	addresses: Addresses,
+	frame: DynamicImage,
+	budget: Budget,
+	stats: Arc<Stats>,
+) -> IoUringFramePainter {
+	let mut frame_painter = IoUringFramePainter::start(addresses, frame, stats);
 
-			 */
-			todo!()
-		}
-		Style::Image => image::open(settings.image.path).context("Failed to load image.")?,
-	};
+	frame_painter.update_dimensions(target.dimension);
+	frame_painter.update_resize_type(settings.resize_type);
+	frame_painter.update_resize_filter(settings.resize_filter.into());
+	frame_painter.update_position(target.offset);
+	frame_painter.update_budget(budget);
+	frame_painter.update_quantizer(settings.quantization.clone());
+	frame_painter.update_alpha_mode(settings.alpha);
+	frame_painter.update_transform(settings.transform);
+	frame_painter.update_stream_count(target.connections);
+	frame_painter
+}
+
+/// One region per layer, ordered like the compositor draws them.
+fn budget(settings: &Settings) -> anyhow::Result<Budget> {
+	if !matches!(settings.content.style, Style::Layers) {
+		return Ok(Budget::default());
+	}
+	if let Some(layer) = settings.layers.iter().find(|layer| !layer.weight.is_finite()) {
+		bail!(
+			"Invalid weight {} of layer {}, expected a finite number",
+			layer.weight,
+			layer.name
+		);
+	}
 
-	let socket_address = format!("{}:{}", settings.host, settings.port)
-		.to_socket_addrs()?
+	let mut layers = settings.layers.iter().collect::<Vec<_>>();
+	layers.sort_by_key(|layer| layer.z);
+	let regions = layers
 		.into_iter()
-		.next()
-		.unwrap();
-	dbg!(socket_address);
-	let mut frame_painter = IoUringFramePainter::start(socket_address, image);
+		.map(|layer| Region {
+			offset: layer.offset,
+			dimension: layer.dimension,
+			weight: layer.weight,
+		})
+		.collect();
+	Ok(Budget {
+		mode: settings.budget,
+		regions,
+	})
+}
 
-	frame_painter.update_dimensions(settings.dimension);
-	frame_painter.update_position(settings.offset);
-	frame_painter.update_stream_count(settings.connections);
+fn frame_source(mode: Mode, settings: &mut Settings, addresses: &Addresses) -> anyhow::Result<Box<dyn FrameSource>> {
+	if let Mode::StdinRaw { width, height } = mode {
+		return Ok(Box::new(RawStdin::new(width, height)?));
+	}
 
-	loop {
-		thread::sleep(Duration::from_secs(10));
+	if let Style::Layers = settings.content.style {
+		let mut layers = Vec::new();
+		for layer in settings.layers.iter_mut() {
+			let offset = settings.offset + layer.offset;
+			let source = content_source(
+				&mut layer.content,
+				layer.dimension,
+				offset,
+				layer.resize_type,
+				layer.resize_filter.into(),
+				addresses,
+			)
+			.with_context(|| format!("Failed to create layer {}.", layer.name))?;
+			layers.push(Layer {
+				name: layer.name.clone(),
+				source,
+				offset: layer.offset,
+				dimension: layer.dimension,
+				z: layer.z,
+				opacity: layer.opacity,
+				resize_type: layer.resize_type,
+				resize_filter: layer.resize_filter.into(),
+			});
+		}
+		return Ok(Box::new(Compositor::new(layers, settings.dimension)));
 	}
+
+	content_source(
+		&mut settings.content,
+		settings.dimension,
+		settings.offset,
+		settings.resize_type,
+		settings.resize_filter.into(),
+		addresses,
+	)
+}
+
+/// `offset` is the position on the canvas, which is needed by styles reading the canvas.
+fn content_source(
+	content: &mut Content,
+	dimension: Dimension,
+	offset: Coordinate,
+	resize_type: ResizeType,
+	resize_filter: FilterType,
+	addresses: &Addresses,
+) -> anyhow::Result<Box<dyn FrameSource>> {
+	let frame_source: Box<dyn FrameSource> = match content.style {
+		Style::Mandelbrot
+		| Style::Julia
+		| Style::BurningShip
+		| Style::Tricorn
+		| Style::Multibrot
+		| Style::Newton
+		| Style::Formula => {
+			let settings = content
+				.fractal
+				.as_mut()
+				.context("Missing [fractal] section for fractal styles.")?;
+			let fractal = FractalType::new(&content.style, settings)?;
+			let width = dimension.width.try_into()?;
+			let height = dimension.height.try_into()?;
+			match settings.animation.take() {
+				Some(animation) => Box::new(Animation::new(fractal, settings.clone(), animation, width, height)?),
+				None => {
+					let view = View {
+						center: settings.center,
+						width: settings.width,
+					};
+					let frame = fractal::render(&fractal, view, settings, width, height, true);
+					Box::new(StaticFrame::from(DynamicImage::ImageRgba8(frame)))
+				}
+			}
+		}
+		Style::Image => {
+			let settings = content
+				.image
+				.as_ref()
+				.context("Missing [image] section for image style.")?;
+			Box::new(StaticFrame::from(
+				image::open(&settings.path).context("Failed to load image.")?,
+			))
+		}
+		Style::Text => {
+			let text = content
+				.text
+				.as_ref()
+				.context("Missing [text] section for text style.")?;
+			Box::new(TextRenderer::new(text, dimension.width.try_into()?)?)
+		}
+		Style::Clock => match content
+			.clock
+			.take()
+			.context("Missing [clock] section for clock style.")?
+		{
+			Clock::Digital(clock) => Box::new(TextWidget::digital_clock(clock)?),
+			Clock::Analog(clock) => {
+				let size = dimension.width.min(dimension.height);
+				Box::new(AnalogClock::new(clock, size.try_into()?))
+			}
+		},
+		Style::Countdown => Box::new(TextWidget::countdown(
+			content
+				.countdown
+				.take()
+				.context("Missing [countdown] section for countdown style.")?,
+		)?),
+		Style::TextFile => Box::new(TextWidget::text_file(
+			content
+				.text_file
+				.take()
+				.context("Missing [text_file] section for text_file style.")?,
+		)?),
+		Style::Playlist => {
+			let playlist = content
+				.playlist
+				.as_ref()
+				.context("Missing [playlist] section for playlist style.")?;
+			let size = playlist::Size {
+				width: dimension.width.try_into()?,
+				height: dimension.height.try_into()?,
+				resize_type,
+				resize_filter,
+			};
+			Box::new(Playlist::new(playlist, size)?)
+		}
+		Style::Video => Box::new(Video::new(
+			content
+				.video
+				.as_ref()
+				.context("Missing [video] section for video style.")?,
+		)?),
+		Style::Plasma | Style::Life | Style::Fire | Style::Starfield | Style::Noise => {
+			let width = dimension.width.try_into()?;
+			let height = dimension.height.try_into()?;
+			let generator = &content.generator;
+			let tick_rate = generator.tick_rate;
+			match content.style {
+				Style::Plasma => Box::new(Animated::new(Plasma::new(&generator.plasma), width, height, tick_rate)?),
+				Style::Life => {
+					let mut life = Life::new(&generator.life, width, height)?;
+					if generator.life.seed_from_canvas {
+						life.seed_from_canvas(addresses, offset, generator.life.canvas_threshold)
+							.context("Failed to seed life from the canvas.")?;
+					}
+					Box::new(Animated::new(life, width, height, tick_rate)?)
+				}
+				Style::Fire => Box::new(Animated::new(
+					Fire::new(&generator.fire, width, height)?,
+					width,
+					height,
+					tick_rate,
+				)?),
+				Style::Starfield => Box::new(Animated::new(
+					Starfield::new(&generator.starfield)?,
+					width,
+					height,
+					tick_rate,
+				)?),
+				_ => Box::new(Animated::new(Noise::new(&generator.noise), width, height, tick_rate)?),
+			}
+		}
+		Style::Layers => anyhow::bail!("The layers style is only supported at the top level."),
+	};
+	Ok(frame_source)
 }
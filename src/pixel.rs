@@ -2,6 +2,8 @@ use crate::coordinate::Coordinate;
 use crate::coordinate::Dimension;
 use image::Rgba;
 use rand::seq::SliceRandom;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer};
 use std::fmt::{Display, Formatter};
 use std::iter::Iterator;
 use std::ops::Index;
@@ -9,7 +11,7 @@ use std::ops::IndexMut;
 use std::option::Option;
 use std::u32;
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug)]
 pub struct Color(Rgba<u8>);
 
 impl From<Rgba<u8>> for Color {
@@ -52,15 +54,6 @@ impl Color {
 		Self::rgb(color, color, color)
 	}
 
-	pub fn gradient24(value: f64) -> Color {
-		let value32 = (value * u32::MAX as f64) as u32;
-		let blue = (value32 & 0xff) as u8;
-		let green = ((value32 >> 8) & 0xff) as u8;
-		let red = ((value32 >> 16) & 0xff) as u8;
-
-		Self::rgb(red, green, blue)
-	}
-
 	#[allow(unused)]
 	pub fn gray_gradient(value: f64) -> Color {
 		let color = (255.0 * value) as u8;
@@ -68,6 +61,33 @@ impl Color {
 	}
 }
 
+impl From<Color> for Rgba<u8> {
+	fn from(color: Color) -> Self {
+		color.0
+	}
+}
+
+/// Deserializes colors from `rrggbb` or `rrggbbaa` hex strings, optionally prefixed with `#`.
+impl<'de> Deserialize<'de> for Color {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		let text = String::deserialize(deserializer)?;
+		let hex = text.trim_start_matches('#');
+		let value = u32::from_str_radix(hex, 16).map_err(|_| D::Error::custom(format!("Invalid color '{text}'")))?;
+		match hex.len() {
+			6 => Ok(Self::rgb((value >> 16) as u8, (value >> 8) as u8, value as u8)),
+			8 => Ok(Self::rgba(
+				(value >> 24) as u8,
+				(value >> 16) as u8,
+				(value >> 8) as u8,
+				value as u8,
+			)),
+			_ => Err(D::Error::custom(format!(
+				"Invalid color '{text}', expected rrggbb or rrggbbaa"
+			))),
+		}
+	}
+}
+
 impl Display for Color {
 	fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
 		match self.alpha() {
@@ -0,0 +1,90 @@
+use crate::Pixel;
+use std::convert::TryFrom;
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+/// Lowercase two digit hex representation of every byte value.
+const HEX: [[u8; 2]; 256] = {
+	let mut table = [[0; 2]; 256];
+	let mut index = 0;
+	while index < table.len() {
+		table[index] = [HEX_DIGITS[index >> 4], HEX_DIGITS[index & 0xf]];
+		index += 1;
+	}
+	table
+};
+
+/// Coordinates below this value are encoded via the lookup table, bigger ones are calculated digit by digit.
+const DECIMAL_TABLE_SIZE: usize = 10_000;
+
+#[derive(Clone, Copy)]
+struct Decimal {
+	digits: [u8; 4],
+	length: u8,
+}
+
+/// Encodes pixels as `PX x y rrggbb\n` commands using precomputed decimal and hex tables
+/// instead of going through the `Display` implementations.
+pub struct PixelEncoder {
+	decimals: Vec<Decimal>,
+}
+
+impl Default for PixelEncoder {
+	fn default() -> Self {
+		let decimals = (0..DECIMAL_TABLE_SIZE)
+			.map(|number| {
+				let text = number.to_string();
+				let mut digits = [0; 4];
+				digits[..text.len()].copy_from_slice(text.as_bytes());
+				Decimal {
+					digits,
+					length: text.len() as u8,
+				}
+			})
+			.collect();
+		Self { decimals }
+	}
+}
+
+impl PixelEncoder {
+	/// Appends the `PX` command for `pixel` to `buffer`, fully transparent pixels and negative coordinates are skipped.
+	pub fn encode(&self, pixel: Pixel, buffer: &mut Vec<u8>) {
+		let color = pixel.color;
+		let (Ok(x), Ok(y)) = (usize::try_from(pixel.coordinate.x), usize::try_from(pixel.coordinate.y)) else {
+			return;
+		};
+		if color.alpha() == 0 {
+			return;
+		}
+
+		buffer.extend_from_slice(b"PX ");
+		self.encode_decimal(x, buffer);
+		buffer.push(b' ');
+		self.encode_decimal(y, buffer);
+		buffer.push(b' ');
+		buffer.extend_from_slice(&HEX[usize::from(color.red())]);
+		buffer.extend_from_slice(&HEX[usize::from(color.green())]);
+		buffer.extend_from_slice(&HEX[usize::from(color.blue())]);
+		if color.alpha() != u8::MAX {
+			buffer.extend_from_slice(&HEX[usize::from(color.alpha())]);
+		}
+		buffer.push(b'\n');
+	}
+
+	fn encode_decimal(&self, number: usize, buffer: &mut Vec<u8>) {
+		if let Some(Decimal { digits, length }) = self.decimals.get(number) {
+			buffer.extend_from_slice(&digits[..usize::from(*length)]);
+			return;
+		}
+
+		let mut digits = [0; 20];
+		let mut start = digits.len();
+		let mut remaining = number;
+		while remaining > 0 {
+			start -= 1;
+			digits[start] = b'0' + (remaining % 10) as u8;
+			remaining /= 10;
+		}
+		buffer.extend_from_slice(&digits[start..]);
+	}
+}
@@ -0,0 +1,315 @@
+use crate::frame_painter::{resize_frame, ResizeType};
+use crate::frame_source::FrameSource;
+use crate::settings;
+use crate::settings::Transition;
+use anyhow::{bail, Context};
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::imageops;
+use image::imageops::FilterType;
+use image::{AnimationDecoder, DynamicImage, Frame, ImageFormat, RgbaImage};
+use rand::rngs::SmallRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+const TICK_INTERVAL: Duration = Duration::from_millis(40);
+
+/// Shows a list of images and animations one after another.
+pub struct Playlist {
+	items: Vec<Item>,
+	/// Indices into `items` in the order they are shown
+	order: Vec<usize>,
+	position: usize,
+	default_duration: Duration,
+	transition: Transition,
+	transition_duration: Duration,
+	shuffle: bool,
+	repeat: bool,
+	size: Size,
+	current: LoadedItem,
+	next: Option<(usize, LoadedItem)>,
+	item_start: Instant,
+	last_shown: Option<Shown>,
+	rng: SmallRng,
+}
+
+struct Item {
+	path: PathBuf,
+	duration: Option<Duration>,
+}
+
+/// Everything needed to resize the items exactly like the painter would.
+#[derive(Clone, Copy)]
+pub struct Size {
+	pub width: u32,
+	pub height: u32,
+	pub resize_type: ResizeType,
+	pub resize_filter: FilterType,
+}
+
+struct LoadedItem {
+	/// Resized frames with the time they are shown
+	frames: Vec<(RgbaImage, Duration)>,
+	duration: Duration,
+}
+
+#[derive(PartialEq)]
+enum Shown {
+	Frame {
+		position: usize,
+		frame: usize,
+	},
+	/// Crossfades always produce a new frame
+	Transition,
+	/// Last item of a non repeating playlist that has finished
+	End,
+}
+
+impl Playlist {
+	pub fn new(settings: &settings::Playlist, size: Size) -> anyhow::Result<Self> {
+		let mut items = Vec::new();
+		if let Some(directory) = &settings.directory {
+			let mut paths = fs::read_dir(directory)
+				.with_context(|| format!("Failed to read playlist directory '{directory}'"))?
+				.map(|entry| entry.map(|entry| entry.path()))
+				.collect::<Result<Vec<_>, _>>()?;
+			paths.retain(|path| path.is_file() && ImageFormat::from_path(path).is_ok());
+			paths.sort();
+			items.extend(paths.into_iter().map(|path| Item { path, duration: None }));
+		}
+		for item in &settings.items {
+			let duration = item
+				.duration
+				.map(Duration::try_from_secs_f64)
+				.transpose()
+				.with_context(|| format!("Invalid duration of playlist item '{}'", item.path))?;
+			items.push(Item {
+				path: PathBuf::from(&item.path),
+				duration,
+			});
+		}
+		if items.is_empty() {
+			bail!("Playlist is empty.");
+		}
+
+		let default_duration = Duration::try_from_secs_f64(settings.duration).context("Invalid playlist duration")?;
+		let transition_duration = Duration::try_from_secs_f64(settings.transition_duration)
+			.context("Invalid playlist transition_duration")?;
+		let mut playlist = Self {
+			order: (0..items.len()).collect(),
+			items,
+			position: 0,
+			default_duration,
+			transition: settings.transition,
+			transition_duration,
+			shuffle: settings.shuffle,
+			repeat: settings.repeat,
+			size,
+			current: LoadedItem {
+				frames: Vec::new(),
+				duration: Duration::ZERO,
+			},
+			next: None,
+			item_start: Instant::now(),
+			last_shown: None,
+			rng: SmallRng::from_entropy(),
+		};
+		if playlist.shuffle {
+			playlist.order.shuffle(&mut playlist.rng);
+		}
+		let (position, current) = playlist
+			.load_from(0, playlist.order.len())
+			.context("None of the playlist items could be loaded.")?;
+		playlist.position = position;
+		playlist.current = current;
+		playlist.next = playlist.load_next();
+		Ok(playlist)
+	}
+
+	/// Loads the first of the next `count` items starting at `position` in the order that can be loaded.
+	fn load_from(&self, position: usize, count: usize) -> Option<(usize, LoadedItem)> {
+		for offset in 0..count {
+			let position = (position + offset) % self.order.len();
+			let item = &self.items[self.order[position]];
+			match load_item(item, self.default_duration, self.size) {
+				Ok(loaded) => return Some((position, loaded)),
+				Err(error) => println!("Skipping playlist item '{}': {error:#}", item.path.display()),
+			}
+		}
+		None
+	}
+
+	/// Loads the item after the current one, `None` at the end of a non repeating playlist.
+	fn load_next(&self) -> Option<(usize, LoadedItem)> {
+		let remaining = if self.repeat {
+			self.order.len()
+		} else {
+			self.order.len() - self.position - 1
+		};
+		self.load_from(self.position + 1, remaining)
+	}
+
+	fn advance(&mut self) {
+		let Some((position, next)) = self.next.take() else {
+			return;
+		};
+
+		let wrapped = position <= self.position;
+		self.current = next;
+		self.position = position;
+		self.item_start = Instant::now();
+		if wrapped && self.shuffle {
+			// reshuffle for the next round, but keep the item that is already being shown in place
+			let current = self.order[position];
+			self.order.shuffle(&mut self.rng);
+			if let Some(index) = self.order.iter().position(|&index| index == current) {
+				self.order.swap(position, index);
+			}
+		}
+		self.next = self.load_next();
+	}
+}
+
+impl FrameSource for Playlist {
+	fn next_frame(&mut self) -> anyhow::Result<Option<DynamicImage>> {
+		if self.item_start.elapsed() >= self.current.duration && self.next.is_some() {
+			self.advance();
+		}
+
+		let elapsed = self.item_start.elapsed();
+		let remaining = self.current.duration.saturating_sub(elapsed);
+		let (frame_index, frame) = self.current.frame_at(elapsed);
+
+		let (shown, frame) = match (&self.next, self.transition) {
+			(None, _) if remaining.is_zero() => (Shown::End, frame.clone()),
+			(Some((_, next)), Transition::Crossfade) if remaining < self.transition_duration => {
+				let progress = 1.0 - remaining.as_secs_f32() / self.transition_duration.as_secs_f32();
+				(Shown::Transition, crossfade(frame, &next.frames[0].0, progress))
+			}
+			_ => (
+				Shown::Frame {
+					position: self.position,
+					frame: frame_index,
+				},
+				frame.clone(),
+			),
+		};
+
+		if shown != Shown::Transition && self.last_shown.as_ref() == Some(&shown) {
+			return Ok(None);
+		}
+		self.last_shown = Some(shown);
+		Ok(Some(DynamicImage::ImageRgba8(frame)))
+	}
+
+	fn interval(&self) -> Option<Duration> {
+		Some(TICK_INTERVAL)
+	}
+}
+
+impl LoadedItem {
+	fn frame_at(&self, elapsed: Duration) -> (usize, &RgbaImage) {
+		let animation_length = self.frames.iter().map(|(_, delay)| *delay).sum::<Duration>();
+		if self.frames.len() == 1 || animation_length.is_zero() {
+			return (0, &self.frames[0].0);
+		}
+
+		// animations loop for as long as the item is shown
+		let mut time_in_animation = Duration::from_nanos((elapsed.as_nanos() % animation_length.as_nanos()) as u64);
+		for (index, (frame, delay)) in self.frames.iter().enumerate() {
+			if time_in_animation < *delay {
+				return (index, frame);
+			}
+			time_in_animation -= *delay;
+		}
+		(self.frames.len() - 1, &self.frames[self.frames.len() - 1].0)
+	}
+}
+
+fn load_item(item: &Item, default_duration: Duration, size: Size) -> anyhow::Result<LoadedItem> {
+	let frames = load_frames(&item.path)?;
+	let resize = |frame: &DynamicImage| {
+		let resized = resize_frame(frame, size.resize_type, size.resize_filter, size.width, size.height);
+		if resized.dimensions() == (size.width, size.height) {
+			return resized;
+		}
+
+		// center frames that keep their aspect ratio, so all frames can be crossfaded
+		let mut centered = RgbaImage::new(size.width, size.height);
+		let x = (size.width - resized.width()) / 2;
+		let y = (size.height - resized.height()) / 2;
+		imageops::overlay(&mut centered, &resized, x.into(), y.into());
+		centered
+	};
+
+	let (frames, duration) = match frames {
+		Frames::Still(image) => (vec![(resize(&image), default_duration)], default_duration),
+		Frames::Animation(frames) => {
+			let frames = frames
+				.into_iter()
+				.map(|frame| {
+					let delay = Duration::from(frame.delay());
+					(resize(&DynamicImage::ImageRgba8(frame.into_buffer())), delay)
+				})
+				.collect::<Vec<_>>();
+			let animation_length = frames.iter().map(|(_, delay)| *delay).sum();
+			(frames, animation_length)
+		}
+	};
+	if frames.is_empty() {
+		bail!("Animation has no frames.");
+	}
+
+	Ok(LoadedItem {
+		frames,
+		duration: item.duration.unwrap_or(duration),
+	})
+}
+
+enum Frames {
+	Still(DynamicImage),
+	Animation(Vec<Frame>),
+}
+
+fn load_frames(path: &Path) -> anyhow::Result<Frames> {
+	let animation = match ImageFormat::from_path(path)? {
+		ImageFormat::Gif => Some(GifDecoder::new(BufReader::new(File::open(path)?))?.into_frames()),
+		ImageFormat::Png => {
+			let decoder = PngDecoder::new(BufReader::new(File::open(path)?))?;
+			if decoder.is_apng() {
+				Some(decoder.apng().into_frames())
+			} else {
+				None
+			}
+		}
+		_ => None,
+	};
+
+	match animation {
+		Some(frames) => {
+			let mut frames = frames.collect_frames()?;
+			if frames.len() == 1 {
+				let frame = frames.remove(0);
+				return Ok(Frames::Still(DynamicImage::ImageRgba8(frame.into_buffer())));
+			}
+			Ok(Frames::Animation(frames))
+		}
+		None => Ok(Frames::Still(image::open(path)?)),
+	}
+}
+
+fn crossfade(from: &RgbaImage, to: &RgbaImage, progress: f32) -> RgbaImage {
+	let mut blended = from.clone();
+	for (pixel, target) in blended.pixels_mut().zip(to.pixels()) {
+		for channel in 0..4 {
+			let from = f32::from(pixel[channel]);
+			let to = f32::from(target[channel]);
+			pixel[channel] = (from + (to - from) * progress).round() as u8;
+		}
+	}
+	blended
+}
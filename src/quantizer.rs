@@ -0,0 +1,206 @@
+use crate::pixel::Color;
+use image::{Rgba, RgbaImage};
+use serde::Deserialize;
+
+/// Reduces frames to a limited palette before they are serialized.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Quantizer {
+	pub palette: Palette,
+	#[serde(default)]
+	pub dithering: Dithering,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Palette {
+	/// Fixed list of colors, given as `rrggbb` hex strings.
+	Fixed { colors: Vec<Color> },
+	/// Palette with the given number of colors, calculated from every frame via median cut.
+	MedianCut { colors: usize },
+	/// Evenly spaced gray levels from black to white.
+	Grayscale { levels: usize },
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Dithering {
+	#[default]
+	None,
+	FloydSteinberg,
+	Ordered,
+}
+
+/// 4x4 Bayer matrix used for ordered dithering.
+const BAYER: [[f32; 4]; 4] = [
+	[0.0, 8.0, 2.0, 10.0],
+	[12.0, 4.0, 14.0, 6.0],
+	[3.0, 11.0, 1.0, 9.0],
+	[15.0, 7.0, 13.0, 5.0],
+];
+
+impl Quantizer {
+	pub fn quantize(&self, frame: &RgbaImage) -> RgbaImage {
+		let palette = self.palette.colors(frame);
+		if palette.is_empty() {
+			return frame.clone();
+		}
+
+		match self.dithering {
+			Dithering::None => quantize_plain(frame, &palette),
+			Dithering::FloydSteinberg => quantize_floyd_steinberg(frame, &palette),
+			Dithering::Ordered => quantize_ordered(frame, &palette),
+		}
+	}
+}
+
+impl Palette {
+	fn colors(&self, frame: &RgbaImage) -> Vec<[u8; 3]> {
+		match self {
+			Palette::Fixed { colors } => colors
+				.iter()
+				.map(|color| [color.red(), color.green(), color.blue()])
+				.collect(),
+			Palette::MedianCut { colors } => median_cut(frame, *colors),
+			Palette::Grayscale { levels } => match levels {
+				0 => Vec::new(),
+				1 => vec![[0; 3]],
+				levels => (0..*levels)
+					.map(|level| {
+						let gray = (level * usize::from(u8::MAX) / (levels - 1)) as u8;
+						[gray; 3]
+					})
+					.collect(),
+			},
+		}
+	}
+}
+
+fn quantize_plain(frame: &RgbaImage, palette: &[[u8; 3]]) -> RgbaImage {
+	let mut quantized = frame.clone();
+	for pixel in quantized.pixels_mut().filter(|pixel| pixel[3] != 0) {
+		let [red, green, blue] = nearest_color(palette, [pixel[0].into(), pixel[1].into(), pixel[2].into()]);
+		*pixel = Rgba([red, green, blue, pixel[3]]);
+	}
+	quantized
+}
+
+fn quantize_floyd_steinberg(frame: &RgbaImage, palette: &[[u8; 3]]) -> RgbaImage {
+	let width = frame.width() as usize;
+	let height = frame.height() as usize;
+	let mut errors = vec![[0f32; 3]; width * height];
+	let mut quantized = frame.clone();
+
+	for y in 0..height {
+		for x in 0..width {
+			let pixel = quantized.get_pixel_mut(x as u32, y as u32);
+			if pixel[3] == 0 {
+				continue;
+			}
+
+			let error = errors[y * width + x];
+			let wanted = [0, 1, 2].map(|channel| f32::from(pixel[channel]) + error[channel]);
+			let color = nearest_color(palette, wanted);
+			*pixel = Rgba([color[0], color[1], color[2], pixel[3]]);
+
+			let difference = [0, 1, 2].map(|channel| wanted[channel] - f32::from(color[channel]));
+			let mut diffuse = |x: usize, y: usize, factor: f32| {
+				if x < width && y < height {
+					let error = &mut errors[y * width + x];
+					for channel in 0..3 {
+						error[channel] += difference[channel] * factor;
+					}
+				}
+			};
+			diffuse(x + 1, y, 7.0 / 16.0);
+			if let Some(left) = x.checked_sub(1) {
+				diffuse(left, y + 1, 3.0 / 16.0);
+			}
+			diffuse(x, y + 1, 5.0 / 16.0);
+			diffuse(x + 1, y + 1, 1.0 / 16.0);
+		}
+	}
+
+	quantized
+}
+
+fn quantize_ordered(frame: &RgbaImage, palette: &[[u8; 3]]) -> RgbaImage {
+	// approximate distance between neighbouring palette colors, assuming they are evenly spread
+	let spread = f32::from(u8::MAX) / (palette.len() as f32).cbrt();
+	let mut quantized = frame.clone();
+	for (x, y, pixel) in quantized.enumerate_pixels_mut() {
+		if pixel[3] == 0 {
+			continue;
+		}
+
+		let threshold = (BAYER[y as usize % 4][x as usize % 4] + 0.5) / 16.0 - 0.5;
+		let wanted = [0, 1, 2].map(|channel| f32::from(pixel[channel]) + threshold * spread);
+		let [red, green, blue] = nearest_color(palette, wanted);
+		*pixel = Rgba([red, green, blue, pixel[3]]);
+	}
+	quantized
+}
+
+fn nearest_color(palette: &[[u8; 3]], wanted: [f32; 3]) -> [u8; 3] {
+	let distance = |color: &[u8; 3]| {
+		(0..3)
+			.map(|channel| (f32::from(color[channel]) - wanted[channel]).powi(2))
+			.sum::<f32>()
+	};
+	palette
+		.iter()
+		.copied()
+		.min_by(|left, right| distance(left).total_cmp(&distance(right)))
+		.unwrap_or_default()
+}
+
+fn median_cut(frame: &RgbaImage, color_count: usize) -> Vec<[u8; 3]> {
+	let colors = frame
+		.pixels()
+		.filter(|pixel| pixel[3] != 0)
+		.map(|pixel| [pixel[0], pixel[1], pixel[2]])
+		.collect::<Vec<_>>();
+	if colors.is_empty() || color_count == 0 {
+		return Vec::new();
+	}
+
+	let mut boxes = vec![colors];
+	while boxes.len() < color_count {
+		// split the box with the widest channel range at the median of that channel
+		let Some((index, channel, _)) = boxes
+			.iter()
+			.enumerate()
+			.filter(|(_, colors)| colors.len() > 1)
+			.map(|(index, colors)| {
+				let (channel, range) = (0..3)
+					.map(|channel| {
+						let minimum = colors.iter().map(|color| color[channel]).min().unwrap_or_default();
+						let maximum = colors.iter().map(|color| color[channel]).max().unwrap_or_default();
+						(channel, maximum - minimum)
+					})
+					.max_by_key(|(_, range)| *range)
+					.unwrap_or_default();
+				(index, channel, range)
+			})
+			.filter(|(_, _, range)| *range > 0)
+			.max_by_key(|(_, _, range)| *range)
+		else {
+			break;
+		};
+
+		let mut colors = boxes.swap_remove(index);
+		colors.sort_unstable_by_key(|color| color[channel]);
+		let upper = colors.split_off(colors.len() / 2);
+		boxes.push(colors);
+		boxes.push(upper);
+	}
+
+	boxes
+		.iter()
+		.map(|colors| {
+			[0, 1, 2].map(|channel| {
+				let sum = colors.iter().map(|color| u64::from(color[channel])).sum::<u64>();
+				(sum / colors.len() as u64) as u8
+			})
+		})
+		.collect()
+}
@@ -0,0 +1,61 @@
+use crate::frame_source::FrameSource;
+use anyhow::{bail, Context};
+use image::{DynamicImage, RgbaImage};
+use std::io::{self, ErrorKind, Read, Stdin};
+use std::time::Duration;
+
+/// Reads consecutive raw RGBA frames of a fixed size from stdin.
+///
+/// Frames are read as fast as they arrive, the painter drops the ones it can't keep up with.
+pub struct RawStdin {
+	stdin: Stdin,
+	width: u32,
+	height: u32,
+	ended: bool,
+}
+
+impl RawStdin {
+	pub fn new(width: u32, height: u32) -> anyhow::Result<Self> {
+		if width == 0 || height == 0 {
+			bail!("Frame size {width}x{height} is empty.");
+		}
+
+		Ok(Self {
+			stdin: io::stdin(),
+			width,
+			height,
+			ended: false,
+		})
+	}
+}
+
+impl FrameSource for RawStdin {
+	fn next_frame(&mut self) -> anyhow::Result<Option<DynamicImage>> {
+		if self.ended {
+			return Ok(None);
+		}
+
+		let mut buffer = vec![0; self.width as usize * self.height as usize * 4];
+		match self.stdin.lock().read_exact(&mut buffer) {
+			Ok(()) => {}
+			Err(error) if error.kind() == ErrorKind::UnexpectedEof => {
+				println!("Stdin closed, keeping the last frame.");
+				self.ended = true;
+				return Ok(None);
+			}
+			Err(error) => return Err(error).context("Failed to read frame from stdin"),
+		}
+
+		let frame = RgbaImage::from_raw(self.width, self.height, buffer).context("Frame buffer has the wrong size")?;
+		Ok(Some(DynamicImage::ImageRgba8(frame)))
+	}
+
+	fn interval(&self) -> Option<Duration> {
+		if self.ended {
+			None
+		} else {
+			// reading blocks until the next frame is available
+			Some(Duration::ZERO)
+		}
+	}
+}
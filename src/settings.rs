@@ -1,6 +1,16 @@
+use crate::address::Source;
+use crate::alpha::AlphaMode;
+use crate::budget::BudgetMode;
 use crate::complex::Complex;
+use crate::double_double::DoubleDouble;
+use crate::fractal::coloring::Coloring;
+use crate::frame_painter::ResizeType;
+use crate::pixel::Color;
+use crate::quantizer::Quantizer;
+use crate::transform::Transform;
 use crate::Coordinate;
 use crate::Dimension;
+use image::imageops::FilterType;
 use serde::Deserialize;
 use std::fs::File;
 use std::io::Read;
@@ -9,13 +19,139 @@ use std::io::Read;
 pub struct Settings {
 	pub host: String,
 	pub port: u16,
-	pub fractal: Fractal,
-	pub image: Image,
-	pub style: Style,
+	/// Try the IPv6 addresses of the servers first instead of following the order of the resolver
+	#[serde(default)]
+	pub prefer_ipv6: bool,
+	/// Local addresses like `192.0.2.1` or IPv6 prefixes like `2001:db8::/64` the connections are bound to,
+	/// one after another. Prefixes need a local route, like `ip -6 route add local 2001:db8::/64 dev lo`.
+	#[serde(default)]
+	pub source_addresses: Vec<Source>,
+	#[serde(flatten)]
+	pub content: Content,
 	pub dimension: Dimension,
+	#[serde(default)]
+	pub resize_type: ResizeType,
+	#[serde(default)]
+	pub resize_filter: ResizeFilter,
 	pub offset: Coordinate,
 	pub connections: usize,
+	/// How the connections are shared between the layers
+	#[serde(default)]
+	pub budget: BudgetMode,
 	pub timeout: u64,
+	#[serde(default)]
+	pub quantization: Option<Quantizer>,
+	#[serde(default)]
+	pub alpha: AlphaMode,
+	#[serde(default)]
+	pub transform: Transform,
+	/// Drawn on top of each other into one frame of `dimension` by the layers style
+	#[serde(default)]
+	pub layers: Vec<Layer>,
+	/// Servers painted in addition to `host`
+	#[serde(default)]
+	pub targets: Vec<Target>,
+	/// Seconds between two reports of the throughput of every server, 0 disables them
+	#[serde(default = "Settings::default_stats_interval")]
+	pub stats_interval: f64,
+}
+
+/// Another Pixelflut server
+#[derive(Debug, Deserialize)]
+pub struct Target {
+	/// Shown in the stats instead of `host:port`
+	#[serde(default)]
+	pub name: Option<String>,
+	pub host: String,
+	pub port: u16,
+	pub connections: usize,
+	pub offset: Coordinate,
+	pub dimension: Dimension,
+	/// Paints its own content instead of sharing the one configured at the top level
+	#[serde(default)]
+	pub content: Option<Content>,
+}
+
+/// What is drawn, the sections only need to be present for the styles that use them
+#[derive(Debug, Deserialize)]
+pub struct Content {
+	pub style: Style,
+	#[serde(default)]
+	pub fractal: Option<Fractal>,
+	#[serde(default)]
+	pub image: Option<Image>,
+	#[serde(default)]
+	pub text: Option<Text>,
+	#[serde(default)]
+	pub clock: Option<Clock>,
+	#[serde(default)]
+	pub countdown: Option<Countdown>,
+	#[serde(default)]
+	pub text_file: Option<TextFile>,
+	#[serde(default)]
+	pub playlist: Option<Playlist>,
+	#[serde(default)]
+	pub video: Option<Video>,
+	#[serde(default)]
+	pub generator: Generator,
+}
+
+/// Region of the layers style with its own content
+#[derive(Debug, Deserialize)]
+pub struct Layer {
+	pub name: String,
+	#[serde(flatten)]
+	pub content: Content,
+	/// Position inside the frame of the layers style
+	#[serde(default)]
+	pub offset: Coordinate,
+	pub dimension: Dimension,
+	/// Layers with a higher z are drawn on top, equal ones in the order of the config
+	#[serde(default)]
+	pub z: i32,
+	/// From 0.0 for invisible to 1.0 for the unchanged content
+	#[serde(default = "Layer::default_opacity")]
+	pub opacity: f32,
+	/// Share of the connections, or the painting order with the priority budget
+	#[serde(default = "Layer::default_weight")]
+	pub weight: f64,
+	#[serde(default)]
+	pub resize_type: ResizeType,
+	#[serde(default)]
+	pub resize_filter: ResizeFilter,
+}
+
+impl Layer {
+	fn default_opacity() -> f32 {
+		1.0
+	}
+
+	fn default_weight() -> f64 {
+		1.0
+	}
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResizeFilter {
+	Nearest,
+	Triangle,
+	CatmullRom,
+	Gaussian,
+	#[default]
+	Lanczos3,
+}
+
+impl From<ResizeFilter> for FilterType {
+	fn from(filter: ResizeFilter) -> Self {
+		match filter {
+			ResizeFilter::Nearest => FilterType::Nearest,
+			ResizeFilter::Triangle => FilterType::Triangle,
+			ResizeFilter::CatmullRom => FilterType::CatmullRom,
+			ResizeFilter::Gaussian => FilterType::Gaussian,
+			ResizeFilter::Lanczos3 => FilterType::Lanczos3,
+		}
+	}
 }
 
 #[derive(Debug, Deserialize)]
@@ -23,11 +159,438 @@ pub struct Image {
 	pub path: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize)]
 pub struct Fractal {
 	pub initial_value: Complex,
 	pub iterations: u32,
 	pub active_threshold: f64,
+	/// Point in the middle of the frame, the parts can be strings to give them more digits than f64 has
+	#[serde(default = "Fractal::default_center")]
+	pub center: Complex<DoubleDouble>,
+	/// Width of the visible part of the complex plane
+	#[serde(default = "Fractal::default_width")]
+	pub width: f64,
+	/// Render the fractal continuously instead of once
+	#[serde(default)]
+	pub animation: Option<FractalAnimation>,
+	/// Frames per second of animations
+	#[serde(default = "Fractal::default_frame_rate")]
+	pub frame_rate: f64,
+	#[serde(default)]
+	pub multibrot: Multibrot,
+	#[serde(default)]
+	pub newton: Newton,
+	#[serde(default)]
+	pub coloring: Coloring,
+	/// Iterate the pixels relative to a high precision reference orbit, for zooming deeper than f64 allows.
+	/// Only for the mandelbrot style.
+	#[serde(default)]
+	pub deep_zoom: bool,
+	/// Iteration formula of the formula style with `z` and `c`, like `z^3 + c*sin(z)`
+	#[serde(default)]
+	pub formula: Option<String>,
+	/// Escape radius of the formula style
+	#[serde(default = "Fractal::default_bailout")]
+	pub bailout: f64,
+	/// Start the formula at z = pixel with c = `initial_value` like Julia sets, instead of z = 0 and c = pixel
+	#[serde(default)]
+	pub formula_julia: bool,
+}
+
+impl Fractal {
+	fn default_center() -> Complex<DoubleDouble> {
+		Complex {
+			real: 0.0.into(),
+			imag: 0.0.into(),
+		}
+	}
+
+	fn default_width() -> f64 {
+		4.0
+	}
+
+	fn default_frame_rate() -> f64 {
+		10.0
+	}
+
+	fn default_bailout() -> f64 {
+		256.0
+	}
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Multibrot {
+	/// Power of z in z^n + c
+	pub exponent: u32,
+}
+
+impl Default for Multibrot {
+	fn default() -> Self {
+		Self { exponent: 3 }
+	}
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Newton {
+	/// Coefficients of the polynomial starting with the highest degree
+	pub coefficients: Vec<Complex>,
+}
+
+impl Default for Newton {
+	fn default() -> Self {
+		let coefficient = |real| Complex { real, imag: 0.0 };
+		// z^3 - 1
+		Self {
+			coefficients: vec![coefficient(1.0), coefficient(0.0), coefficient(0.0), coefficient(-1.0)],
+		}
+	}
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FractalAnimation {
+	Zoom(FractalZoom),
+	JuliaSweep(JuliaSweep),
+}
+
+/// Zooms into `target`, starting over when `max_zoom` is reached
+#[derive(Clone, Debug, Deserialize)]
+pub struct FractalZoom {
+	pub target: Complex<DoubleDouble>,
+	/// Zoom factor per second
+	#[serde(default = "FractalZoom::default_speed")]
+	pub speed: f64,
+	/// f64 runs out of precision at about 1e13, deep zooms at about 1e28
+	#[serde(default = "FractalZoom::default_max_zoom")]
+	pub max_zoom: f64,
+}
+
+impl FractalZoom {
+	fn default_speed() -> f64 {
+		1.5
+	}
+
+	fn default_max_zoom() -> f64 {
+		1e12
+	}
+}
+
+/// Moves the Julia `initial_value` along a closed path through `path`
+#[derive(Clone, Debug, Deserialize)]
+pub struct JuliaSweep {
+	pub path: Vec<Complex>,
+	/// Seconds for going around the path once
+	#[serde(default = "JuliaSweep::default_period")]
+	pub period: f64,
+}
+
+impl JuliaSweep {
+	fn default_period() -> f64 {
+		30.0
+	}
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Text {
+	pub text: String,
+	#[serde(flatten)]
+	pub style: TextStyle,
+	/// Scroll the text through a window of `dimension.width` instead of showing it at once
+	#[serde(default)]
+	pub marquee: Option<Marquee>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TextStyle {
+	/// Path to a TTF or OTF font file
+	pub font: String,
+	/// Font size in pixels
+	pub size: f32,
+	#[serde(default = "TextStyle::default_foreground")]
+	pub foreground: Color,
+	/// Transparent if not set
+	#[serde(default)]
+	pub background: Option<Color>,
+	#[serde(default)]
+	pub outline: Option<Outline>,
+}
+
+impl TextStyle {
+	fn default_foreground() -> Color {
+		Color::rgb(u8::MAX, u8::MAX, u8::MAX)
+	}
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Outline {
+	pub color: Color,
+	/// Width in pixels
+	pub width: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Marquee {
+	/// Pixels per second, negative speeds scroll from left to right
+	pub speed: f64,
+	#[serde(default = "Marquee::default_frame_rate")]
+	pub frame_rate: f64,
+}
+
+impl Marquee {
+	fn default_frame_rate() -> f64 {
+		10.0
+	}
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "face", rename_all = "snake_case")]
+pub enum Clock {
+	Digital(DigitalClock),
+	Analog(AnalogClock),
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DigitalClock {
+	/// strftime style format string
+	#[serde(default = "DigitalClock::default_format")]
+	pub format: String,
+	#[serde(flatten)]
+	pub style: TextStyle,
+}
+
+impl DigitalClock {
+	fn default_format() -> String {
+		"%H:%M:%S".to_owned()
+	}
+}
+
+/// Drawn as a square of the smaller side of `dimension`
+#[derive(Debug, Deserialize)]
+pub struct AnalogClock {
+	#[serde(default = "AnalogClock::default_foreground")]
+	pub foreground: Color,
+	#[serde(default = "AnalogClock::default_second_hand")]
+	pub second_hand: Color,
+	/// Transparent if not set
+	#[serde(default)]
+	pub background: Option<Color>,
+}
+
+impl AnalogClock {
+	fn default_foreground() -> Color {
+		Color::rgb(u8::MAX, u8::MAX, u8::MAX)
+	}
+
+	fn default_second_hand() -> Color {
+		Color::rgb(u8::MAX, 0, 0)
+	}
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Countdown {
+	/// Local time in the format `YYYY-MM-DD HH:MM:SS`
+	pub target: String,
+	/// Shown once the target time has been reached
+	#[serde(default)]
+	pub finished_text: String,
+	#[serde(flatten)]
+	pub style: TextStyle,
+}
+
+/// Shows the contents of a text file, updated whenever the file changes
+#[derive(Debug, Deserialize)]
+pub struct TextFile {
+	pub path: String,
+	#[serde(flatten)]
+	pub style: TextStyle,
+}
+
+/// Images and animations from a directory and/or an explicit list, shown one after another
+#[derive(Debug, Deserialize)]
+pub struct Playlist {
+	/// All images in this directory are added in alphabetical order
+	#[serde(default)]
+	pub directory: Option<String>,
+	#[serde(default)]
+	pub items: Vec<PlaylistItem>,
+	/// Seconds every still image is shown unless the item specifies otherwise
+	#[serde(default = "Playlist::default_duration")]
+	pub duration: f64,
+	#[serde(default)]
+	pub transition: Transition,
+	/// Seconds a crossfade takes
+	#[serde(default = "Playlist::default_transition_duration")]
+	pub transition_duration: f64,
+	#[serde(default)]
+	pub shuffle: bool,
+	#[serde(default = "Playlist::default_repeat", rename = "loop")]
+	pub repeat: bool,
+}
+
+impl Playlist {
+	fn default_duration() -> f64 {
+		10.0
+	}
+
+	fn default_transition_duration() -> f64 {
+		1.0
+	}
+
+	fn default_repeat() -> bool {
+		true
+	}
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PlaylistItem {
+	pub path: String,
+	/// Seconds, defaults to one pass for animations and the playlist duration for still images
+	#[serde(default)]
+	pub duration: Option<f64>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Transition {
+	#[default]
+	Cut,
+	Crossfade,
+}
+
+/// Either a Y4M video or a numbered image sequence
+#[derive(Debug, Deserialize)]
+pub struct Video {
+	/// Y4M file, `-` reads from stdin
+	#[serde(default)]
+	pub path: Option<String>,
+	/// Pattern for numbered image files like `frames/%05d.png`
+	#[serde(default)]
+	pub sequence: Option<String>,
+	/// Number of the first image of the sequence
+	#[serde(default = "Video::default_start_number")]
+	pub start_number: u64,
+	/// Frames per second, taken from the header of Y4M files if not set
+	#[serde(default)]
+	pub frame_rate: Option<f64>,
+	/// Stdin can't be looped
+	#[serde(default, rename = "loop")]
+	pub repeat: bool,
+}
+
+impl Video {
+	fn default_start_number() -> u64 {
+		1
+	}
+}
+
+/// Parameters of the procedural generators, every one of them has defaults
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Generator {
+	/// Ticks per second
+	pub tick_rate: f64,
+	pub plasma: Plasma,
+	pub life: Life,
+	pub fire: Fire,
+	pub starfield: Starfield,
+	pub noise: Noise,
+}
+
+impl Default for Generator {
+	fn default() -> Self {
+		Self {
+			tick_rate: 20.0,
+			plasma: Plasma::default(),
+			life: Life::default(),
+			fire: Fire::default(),
+			starfield: Starfield::default(),
+			noise: Noise::default(),
+		}
+	}
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Plasma {
+	/// Size of the waves, smaller values make them bigger
+	pub scale: f64,
+	/// Phase change per tick
+	pub speed: f64,
+}
+
+impl Default for Plasma {
+	fn default() -> Self {
+		Self {
+			scale: 0.05,
+			speed: 0.1,
+		}
+	}
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Life {
+	/// Probability of a cell being alive when seeding randomly
+	pub density: f64,
+	pub alive: Color,
+	/// Transparent if not set
+	pub dead: Option<Color>,
+	/// Use the current canvas content as first generation instead of a random one
+	pub seed_from_canvas: bool,
+	/// Minimum brightness of canvas pixels that start out alive
+	pub canvas_threshold: u8,
+}
+
+impl Default for Life {
+	fn default() -> Self {
+		Self {
+			density: 0.3,
+			alive: Color::rgb(u8::MAX, u8::MAX, u8::MAX),
+			dead: Some(Color::rgb(0, 0, 0)),
+			seed_from_canvas: false,
+			canvas_threshold: 128,
+		}
+	}
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Fire {
+	/// Maximum heat lost per row, bigger values make smaller flames
+	pub decay: u8,
+	/// Maximum horizontal drift per row, negative values blow to the left
+	pub wind: i64,
+}
+
+impl Default for Fire {
+	fn default() -> Self {
+		Self { decay: 2, wind: 1 }
+	}
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Starfield {
+	pub stars: usize,
+	/// Depth travelled per tick, the full depth is 1.0
+	pub speed: f64,
+}
+
+impl Default for Starfield {
+	fn default() -> Self {
+		Self {
+			stars: 500,
+			speed: 0.01,
+		}
+	}
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Noise {
+	pub grayscale: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -35,15 +598,38 @@ pub struct Fractal {
 pub enum Style {
 	Mandelbrot,
 	Julia,
+	BurningShip,
+	Tricorn,
+	Multibrot,
+	Newton,
+	Formula,
 	Image,
+	Text,
+	Clock,
+	Countdown,
+	TextFile,
+	Playlist,
+	Video,
+	Plasma,
+	Life,
+	Fire,
+	Starfield,
+	Noise,
+	Layers,
 }
 
 impl Settings {
+	fn default_stats_interval() -> f64 {
+		10.0
+	}
+
 	pub fn new() -> anyhow::Result<Self> {
 		let mut file = File::open("config.toml")?;
 		let mut text = String::new();
 		file.read_to_string(&mut text)?;
 
-		Ok(toml::from_str(&text)?)
+		let settings: Self = toml::from_str(&text)?;
+		settings.transform.validate()?;
+		Ok(settings)
 	}
 }
@@ -0,0 +1,58 @@
+use std::sync::Arc;
+use tokio_uring::buf::IoBuf;
+
+/// Reference counted, immutable buffer that can be handed to io_uring without copying.
+///
+/// Cloning only increments the reference count, so a stream can rewrite the same buffer over and over.
+#[derive(Clone)]
+pub struct SharedBuffer(Arc<Vec<u8>>);
+
+unsafe impl IoBuf for SharedBuffer {
+	fn stable_ptr(&self) -> *const u8 {
+		self.0.as_ptr()
+	}
+
+	fn bytes_init(&self) -> usize {
+		self.0.len()
+	}
+
+	fn bytes_total(&self) -> usize {
+		self.0.len()
+	}
+}
+
+/// Recycles the allocations of `SharedBuffer`s once every stream has let go of them.
+#[derive(Default)]
+pub struct BufferPool {
+	in_use: Vec<Arc<Vec<u8>>>,
+	free: Vec<Vec<u8>>,
+}
+
+impl BufferPool {
+	/// Returns an empty buffer, reusing the allocation of a released buffer if possible.
+	pub fn take(&mut self) -> Vec<u8> {
+		if self.free.is_empty() {
+			self.reclaim();
+		}
+
+		let mut buffer = self.free.pop().unwrap_or_default();
+		buffer.clear();
+		buffer
+	}
+
+	pub fn share(&mut self, buffer: Vec<u8>) -> SharedBuffer {
+		let buffer = Arc::new(buffer);
+		self.in_use.push(buffer.clone());
+		SharedBuffer(buffer)
+	}
+
+	fn reclaim(&mut self) {
+		let (released, in_use) = self
+			.in_use
+			.drain(..)
+			.partition::<Vec<_>, _>(|buffer| Arc::strong_count(buffer) == 1);
+		self.in_use = in_use;
+		self.free
+			.extend(released.into_iter().filter_map(|buffer| Arc::try_unwrap(buffer).ok()));
+	}
+}
@@ -0,0 +1,69 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Counters of one painting target, updated by its painter threads.
+#[derive(Debug, Default)]
+pub struct Stats {
+	name: String,
+	bytes: AtomicU64,
+	frames: AtomicU64,
+	connections: AtomicUsize,
+	failed_connections: AtomicU64,
+}
+
+impl Stats {
+	pub fn new(name: String) -> Self {
+		Self {
+			name,
+			..Self::default()
+		}
+	}
+
+	pub fn add_bytes(&self, bytes: usize) {
+		self.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+	}
+
+	pub fn add_frame(&self) {
+		self.frames.fetch_add(1, Ordering::Relaxed);
+	}
+
+	pub fn connected(&self) {
+		self.connections.fetch_add(1, Ordering::Relaxed);
+	}
+
+	pub fn disconnected(&self) {
+		self.connections.fetch_sub(1, Ordering::Relaxed);
+	}
+
+	pub fn connection_failed(&self) {
+		self.failed_connections.fetch_add(1, Ordering::Relaxed);
+	}
+}
+
+/// Prints the throughput of every target since the last report each `interval`.
+pub fn spawn_reporter(stats: Vec<Arc<Stats>>, interval: Duration) {
+	thread::spawn(move || {
+		let mut last_bytes = vec![0; stats.len()];
+		let mut last_report = Instant::now();
+		loop {
+			thread::sleep(interval);
+			let elapsed = last_report.elapsed().as_secs_f64();
+			last_report = Instant::now();
+			for (stats, last_bytes) in stats.iter().zip(&mut last_bytes) {
+				let bytes = stats.bytes.load(Ordering::Relaxed);
+				let rate = (bytes - *last_bytes) as f64 / elapsed / 1_000_000.0;
+				*last_bytes = bytes;
+				println!(
+					"{}: {:.2} MB/s, {} connections, {} failed connection attempts, {} frames encoded",
+					stats.name,
+					rate,
+					stats.connections.load(Ordering::Relaxed),
+					stats.failed_connections.load(Ordering::Relaxed),
+					stats.frames.load(Ordering::Relaxed),
+				);
+			}
+		}
+	});
+}
@@ -0,0 +1,200 @@
+use crate::frame_source::{frame_interval, FrameSource};
+use crate::pixel::Color;
+use crate::settings;
+use crate::settings::TextStyle;
+use ab_glyph::{point, Font, FontVec, PxScale, ScaleFont};
+use anyhow::{anyhow, bail, Context};
+use image::{DynamicImage, GenericImage, GenericImageView, Rgba, RgbaImage};
+use std::fs;
+use std::time::{Duration, Instant};
+
+/// Renders a text with a TTF/OTF font, optionally scrolling through it as a marquee.
+pub struct TextRenderer {
+	text: RgbaImage,
+	marquee: Option<Marquee>,
+	rendered: bool,
+}
+
+struct Marquee {
+	width: u32,
+	speed: f64,
+	interval: Duration,
+	start: Instant,
+}
+
+impl TextRenderer {
+	/// `width` is the width of the window the marquee scrolls through.
+	pub fn new(settings: &settings::Text, width: u32) -> anyhow::Result<Self> {
+		let font = load_font(&settings.style.font)?;
+		let text = render_text(&font, &settings.text, &settings.style);
+		let marquee = match &settings.marquee {
+			Some(marquee) if !marquee.speed.is_finite() => bail!("Invalid marquee speed {}", marquee.speed),
+			Some(marquee) => Some(Marquee {
+				width,
+				speed: marquee.speed,
+				interval: frame_interval(marquee.frame_rate).context("Invalid marquee frame_rate")?,
+				start: Instant::now(),
+			}),
+			None => None,
+		};
+
+		Ok(Self {
+			text,
+			marquee,
+			rendered: false,
+		})
+	}
+}
+
+impl FrameSource for TextRenderer {
+	fn next_frame(&mut self) -> anyhow::Result<Option<DynamicImage>> {
+		let Some(marquee) = &self.marquee else {
+			if self.rendered {
+				return Ok(None);
+			}
+			self.rendered = true;
+			return Ok(Some(DynamicImage::ImageRgba8(self.text.clone())));
+		};
+
+		// the text enters on the right and scrolls out to the left before starting over,
+		// or the other way around for negative speeds
+		let period = f64::from(self.text.width() + marquee.width);
+		let scrolled = (marquee.start.elapsed().as_secs_f64() * marquee.speed).rem_euclid(period);
+		let text_x = i64::from(marquee.width) - scrolled as i64;
+
+		let mut frame = RgbaImage::new(marquee.width, self.text.height());
+		let visible_start = (-text_x).max(0) as u32;
+		let visible_end = (i64::from(marquee.width) - text_x).clamp(0, i64::from(self.text.width())) as u32;
+		if visible_start < visible_end {
+			let visible = self
+				.text
+				.view(visible_start, 0, visible_end - visible_start, self.text.height());
+			frame.copy_from(&*visible, text_x.max(0) as u32, 0)?;
+		}
+		Ok(Some(DynamicImage::ImageRgba8(frame)))
+	}
+
+	fn interval(&self) -> Option<Duration> {
+		self.marquee.as_ref().map(|marquee| marquee.interval)
+	}
+}
+
+pub fn load_font(path: &str) -> anyhow::Result<FontVec> {
+	let font_data = fs::read(path).with_context(|| format!("Failed to read font file '{path}'"))?;
+	FontVec::try_from_vec(font_data).map_err(|error| anyhow!("Failed to load font '{path}': {error}"))
+}
+
+/// Rasterizes all lines of the text into an image that is just big enough to fit it.
+pub fn render_text(font: &impl Font, text: &str, style: &TextStyle) -> RgbaImage {
+	let font = font.as_scaled(PxScale::from(style.size));
+	let outline_width = style.outline.as_ref().map(|outline| outline.width).unwrap_or(0);
+	let line_height = font.height() + font.line_gap();
+
+	let lines = text.lines().collect::<Vec<_>>();
+	let line_widths = lines
+		.iter()
+		.map(|line| {
+			let mut width = 0.0;
+			let mut previous = None;
+			for character in line.chars() {
+				let glyph_id = font.glyph_id(character);
+				if let Some(previous) = previous {
+					width += font.kern(previous, glyph_id);
+				}
+				width += font.h_advance(glyph_id);
+				previous = Some(glyph_id);
+			}
+			width
+		})
+		.collect::<Vec<_>>();
+	let text_width = line_widths.iter().copied().fold(0.0, f32::max).ceil() as u32;
+	let text_height = (line_height * lines.len() as f32).ceil() as u32;
+	// empty texts still need a valid image
+	let width = (text_width + 2 * outline_width).max(1);
+	let height = (text_height + 2 * outline_width).max(1);
+
+	// coverage of the glyphs in every pixel from 0.0 to 1.0
+	let mut coverage = vec![0f32; (width * height) as usize];
+	for (line_index, line) in lines.iter().enumerate() {
+		let baseline = outline_width as f32 + line_height * line_index as f32 + font.ascent();
+		let mut caret = outline_width as f32;
+		let mut previous = None;
+		for character in line.chars() {
+			let glyph_id = font.glyph_id(character);
+			if let Some(previous) = previous {
+				caret += font.kern(previous, glyph_id);
+			}
+			let glyph = glyph_id.with_scale_and_position(font.scale(), point(caret, baseline));
+			caret += font.h_advance(glyph_id);
+			previous = Some(glyph_id);
+
+			let Some(outlined) = font.outline_glyph(glyph) else {
+				continue;
+			};
+			let bounds = outlined.px_bounds();
+			outlined.draw(|x, y, glyph_coverage| {
+				let x = bounds.min.x as i64 + i64::from(x);
+				let y = bounds.min.y as i64 + i64::from(y);
+				if (0..i64::from(width)).contains(&x) && (0..i64::from(height)).contains(&y) {
+					let index = (y as u32 * width + x as u32) as usize;
+					coverage[index] = (coverage[index] + glyph_coverage).min(1.0);
+				}
+			});
+		}
+	}
+
+	let background = style.background.map(Rgba::from).unwrap_or(Rgba([0, 0, 0, 0]));
+	let mut image = RgbaImage::from_pixel(width, height, background);
+	if let Some(outline) = &style.outline {
+		let outline_coverage = dilate(&coverage, width, height, outline.width);
+		blend_coverage(&mut image, &outline_coverage, outline.color);
+	}
+	blend_coverage(&mut image, &coverage, style.foreground);
+
+	image
+}
+
+/// Grows the coverage by `radius` pixels in every direction, used for drawing outlines.
+fn dilate(coverage: &[f32], width: u32, height: u32, radius: u32) -> Vec<f32> {
+	let radius = radius as i64;
+	let (width, height) = (i64::from(width), i64::from(height));
+	let mut dilated = vec![0f32; coverage.len()];
+	for y in 0..height {
+		for x in 0..width {
+			let mut maximum = 0f32;
+			for neighbour_y in (y - radius).max(0)..=(y + radius).min(height - 1) {
+				for neighbour_x in (x - radius).max(0)..=(x + radius).min(width - 1) {
+					let (dx, dy) = (neighbour_x - x, neighbour_y - y);
+					if dx * dx + dy * dy <= radius * radius {
+						maximum = maximum.max(coverage[(neighbour_y * width + neighbour_x) as usize]);
+					}
+				}
+			}
+			dilated[(y * width + x) as usize] = maximum;
+		}
+	}
+	dilated
+}
+
+/// Draws `color` over `image` with the given per pixel coverage.
+fn blend_coverage(image: &mut RgbaImage, coverage: &[f32], color: Color) {
+	for (pixel, coverage) in image.pixels_mut().zip(coverage) {
+		let alpha = coverage * f32::from(color.alpha()) / 255.0;
+		if alpha <= 0.0 {
+			continue;
+		}
+
+		let background_alpha = f32::from(pixel[3]) / 255.0;
+		let out_alpha = alpha + background_alpha * (1.0 - alpha);
+		let mix = |foreground: u8, background: u8| {
+			((f32::from(foreground) * alpha + f32::from(background) * background_alpha * (1.0 - alpha)) / out_alpha)
+				.round() as u8
+		};
+		*pixel = Rgba([
+			mix(color.red(), pixel[0]),
+			mix(color.green(), pixel[1]),
+			mix(color.blue(), pixel[2]),
+			(out_alpha * 255.0).round() as u8,
+		]);
+	}
+}
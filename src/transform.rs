@@ -0,0 +1,185 @@
+use crate::pixel::Color;
+use anyhow::bail;
+use image::{Rgba, RgbaImage};
+use rayon::prelude::*;
+use serde::Deserialize;
+
+/// Affine transformation applied to frames after resizing: scale, flip and rotate around a pivot.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(default)]
+pub struct Transform {
+	/// Clockwise rotation in degrees
+	pub rotation: f64,
+	pub flip_horizontal: bool,
+	pub flip_vertical: bool,
+	pub scale_x: f64,
+	pub scale_y: f64,
+	/// Point that stays in place, relative to the frame size so 0.5/0.5 is the center
+	pub pivot_x: f64,
+	pub pivot_y: f64,
+	/// Grow the frame so that nothing gets cut off, the pivot is ignored then
+	pub expand: bool,
+	/// Fill for the parts not covered by the transformed frame, transparent if not set
+	pub background: Option<Color>,
+}
+
+impl Default for Transform {
+	fn default() -> Self {
+		Self {
+			rotation: 0.0,
+			flip_horizontal: false,
+			flip_vertical: false,
+			scale_x: 1.0,
+			scale_y: 1.0,
+			pivot_x: 0.5,
+			pivot_y: 0.5,
+			expand: false,
+			background: None,
+		}
+	}
+}
+
+/// Largest width and height of an expanded frame, anything beyond is cut off around the center.
+const MAX_EXPANDED_SIZE: f64 = 8192.0;
+
+/// 2x2 matrix in row major order
+type Matrix = [[f64; 2]; 2];
+
+impl Transform {
+	pub fn validate(&self) -> anyhow::Result<()> {
+		for (name, scale) in [("scale_x", self.scale_x), ("scale_y", self.scale_y)] {
+			if !scale.is_finite() || scale == 0.0 {
+				bail!("Invalid transform {name} {scale}, expected a finite number other than 0");
+			}
+		}
+		if !self.rotation.is_finite() {
+			bail!("Invalid transform rotation {}", self.rotation);
+		}
+		Ok(())
+	}
+
+	pub fn is_identity(&self) -> bool {
+		self.rotation % 360.0 == 0.0
+			&& !self.flip_horizontal
+			&& !self.flip_vertical
+			&& self.scale_x == 1.0
+			&& self.scale_y == 1.0
+	}
+
+	/// Maps frame coordinates to transformed coordinates, without the translation to the pivot.
+	fn matrix(&self) -> Matrix {
+		let scale_x = if self.flip_horizontal {
+			-self.scale_x
+		} else {
+			self.scale_x
+		};
+		let scale_y = if self.flip_vertical {
+			-self.scale_y
+		} else {
+			self.scale_y
+		};
+		let (sin, cos) = self.rotation.to_radians().sin_cos();
+		// rotation * scale, clockwise because y points down
+		[[cos * scale_x, -sin * scale_y], [sin * scale_x, cos * scale_y]]
+	}
+
+	pub fn apply(&self, frame: &RgbaImage) -> RgbaImage {
+		if self.is_identity() {
+			return frame.clone();
+		}
+
+		let background = self.background.map(Rgba::from).unwrap_or(Rgba([0, 0, 0, 0]));
+		let matrix = self.matrix();
+		let determinant = matrix[0][0] * matrix[1][1] - matrix[0][1] * matrix[1][0];
+		let (width, height) = (f64::from(frame.width()), f64::from(frame.height()));
+		if determinant == 0.0 || frame.width() == 0 || frame.height() == 0 {
+			return RgbaImage::from_pixel(frame.width(), frame.height(), background);
+		}
+		let inverse = [
+			[matrix[1][1] / determinant, -matrix[0][1] / determinant],
+			[-matrix[1][0] / determinant, matrix[0][0] / determinant],
+		];
+
+		let pivot = (self.pivot_x * width, self.pivot_y * height);
+		// the pivot ends up at `target_pivot` in the output
+		let (output_width, output_height, target_pivot) = if self.expand {
+			let corners = [(0.0, 0.0), (width, 0.0), (0.0, height), (width, height)]
+				.map(|(x, y)| multiply(matrix, (x - pivot.0, y - pivot.1)));
+			let (min_x, max_x, min_y, max_y) = corners.iter().fold(
+				(f64::MAX, f64::MIN, f64::MAX, f64::MIN),
+				|(min_x, max_x, min_y, max_y), &(x, y)| (min_x.min(x), max_x.max(x), min_y.min(y), max_y.max(y)),
+			);
+			let output_width = (max_x - min_x).round().clamp(1.0, MAX_EXPANDED_SIZE);
+			let output_height = (max_y - min_y).round().clamp(1.0, MAX_EXPANDED_SIZE);
+			let target_pivot = (
+				-min_x - (max_x - min_x - output_width) / 2.0,
+				-min_y - (max_y - min_y - output_height) / 2.0,
+			);
+			(output_width as u32, output_height as u32, target_pivot)
+		} else {
+			(frame.width(), frame.height(), pivot)
+		};
+
+		let mut output = RgbaImage::new(output_width, output_height);
+		output
+			.par_chunks_mut(output_width as usize * 4)
+			.enumerate()
+			.for_each(|(y, row)| {
+				for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+					let target = (x as f64 + 0.5 - target_pivot.0, y as f64 + 0.5 - target_pivot.1);
+					let (source_x, source_y) = multiply(inverse, target);
+					let sampled = sample(frame, source_x + pivot.0 - 0.5, source_y + pivot.1 - 0.5);
+					pixel.copy_from_slice(&over(sampled, background).0);
+				}
+			});
+		output
+	}
+}
+
+fn multiply(matrix: Matrix, (x, y): (f64, f64)) -> (f64, f64) {
+	(matrix[0][0] * x + matrix[0][1] * y, matrix[1][0] * x + matrix[1][1] * y)
+}
+
+/// Bilinear sample at pixel coordinates, everything outside of the frame is transparent.
+///
+/// Interpolates premultiplied colors, so transparent neighbours don't darken the edges.
+fn sample(frame: &RgbaImage, x: f64, y: f64) -> [f64; 4] {
+	let (left, top) = (x.floor(), y.floor());
+	let (fraction_x, fraction_y) = (x - left, y - top);
+	let mut sum = [0.0; 4];
+	for (offset_x, offset_y, weight) in [
+		(0, 0, (1.0 - fraction_x) * (1.0 - fraction_y)),
+		(1, 0, fraction_x * (1.0 - fraction_y)),
+		(0, 1, (1.0 - fraction_x) * fraction_y),
+		(1, 1, fraction_x * fraction_y),
+	] {
+		let (pixel_x, pixel_y) = (left as i64 + offset_x, top as i64 + offset_y);
+		if weight == 0.0
+			|| !(0..i64::from(frame.width())).contains(&pixel_x)
+			|| !(0..i64::from(frame.height())).contains(&pixel_y)
+		{
+			continue;
+		}
+
+		let pixel = frame.get_pixel(pixel_x as u32, pixel_y as u32);
+		let alpha = f64::from(pixel[3]) / 255.0;
+		for channel in 0..3 {
+			sum[channel] += f64::from(pixel[channel]) * alpha * weight;
+		}
+		sum[3] += alpha * weight;
+	}
+	sum
+}
+
+/// Draws the premultiplied `color` over `background`.
+pub(crate) fn over(color: [f64; 4], background: Rgba<u8>) -> Rgba<u8> {
+	let background_alpha = f64::from(background[3]) / 255.0 * (1.0 - color[3]);
+	let alpha = color[3] + background_alpha;
+	if alpha <= 0.0 {
+		return Rgba([0, 0, 0, 0]);
+	}
+
+	let mix =
+		|channel: usize| ((color[channel] + f64::from(background[channel]) * background_alpha) / alpha).round() as u8;
+	Rgba([mix(0), mix(1), mix(2), (alpha * 255.0).round() as u8])
+}
@@ -0,0 +1,316 @@
+use crate::frame_source::{frame_interval, FrameSource};
+use crate::settings;
+use anyhow::{anyhow, bail, Context};
+use image::{DynamicImage, Rgb, RgbImage};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, ErrorKind};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Plays a Y4M video or a numbered image sequence at a fixed frame rate.
+///
+/// Frames whose time has already passed are skipped, so playback keeps its pace when the painter can't keep up.
+/// Updates are also dropped by the painter itself while the network is still busy with older frames.
+pub struct Video {
+	input: Input,
+	frame_rate: f64,
+	interval: Duration,
+	repeat: bool,
+	start: Instant,
+	/// Index of the frame that was returned last
+	position: Option<u64>,
+}
+
+enum Input {
+	Y4m {
+		path: String,
+		reader: Y4mReader<Box<dyn BufRead + Send>>,
+	},
+	Sequence {
+		pattern: String,
+		start_number: u64,
+	},
+}
+
+impl Video {
+	pub fn new(settings: &settings::Video) -> anyhow::Result<Self> {
+		let (input, header_frame_rate) = match (&settings.path, &settings.sequence) {
+			(Some(path), None) => {
+				let reader = open_y4m(path)?;
+				let frame_rate = reader.frame_rate;
+				let path = path.clone();
+				(Input::Y4m { path, reader }, Some(frame_rate))
+			}
+			(None, Some(pattern)) => {
+				if !pattern.contains('%') {
+					bail!("Image sequence pattern '{pattern}' doesn't contain a %d placeholder.");
+				}
+				let input = Input::Sequence {
+					pattern: pattern.clone(),
+					start_number: settings.start_number,
+				};
+				(input, None)
+			}
+			_ => bail!("Video needs either a path or a sequence, but not both."),
+		};
+		let frame_rate = settings
+			.frame_rate
+			.or(header_frame_rate)
+			.context("Frame rate is required for image sequences.")?;
+		let interval = frame_interval(frame_rate).context("Invalid video frame rate")?;
+
+		Ok(Self {
+			input,
+			frame_rate,
+			interval,
+			repeat: settings.repeat,
+			start: Instant::now(),
+			position: None,
+		})
+	}
+
+	fn can_restart(&self) -> bool {
+		match &self.input {
+			Input::Y4m { path, .. } => self.repeat && path != "-",
+			Input::Sequence { .. } => self.repeat,
+		}
+	}
+
+	fn restart(&mut self) -> anyhow::Result<()> {
+		if let Input::Y4m { path, reader } = &mut self.input {
+			*reader = open_y4m(path)?;
+		}
+		self.start = Instant::now();
+		self.position = None;
+		Ok(())
+	}
+}
+
+impl FrameSource for Video {
+	fn next_frame(&mut self) -> anyhow::Result<Option<DynamicImage>> {
+		let target = (self.start.elapsed().as_secs_f64() * self.frame_rate) as u64;
+		if self.position.is_some_and(|position| position >= target) {
+			return Ok(None);
+		}
+
+		let frame = match &mut self.input {
+			Input::Y4m { reader, .. } => {
+				// skip the frames that are already late
+				let next = self.position.map_or(0, |position| position + 1);
+				for _ in next..target {
+					if !reader.read_raw_frame()? {
+						break;
+					}
+				}
+				reader.read_frame()?.map(DynamicImage::ImageRgb8)
+			}
+			Input::Sequence { pattern, start_number } => {
+				let path = sequence_path(pattern, *start_number + target);
+				if Path::new(&path).exists() {
+					Some(image::open(&path).with_context(|| format!("Failed to load frame '{path}'"))?)
+				} else {
+					None
+				}
+			}
+		};
+
+		match frame {
+			Some(frame) => {
+				self.position = Some(target);
+				Ok(Some(frame))
+			}
+			// only restart videos that have shown at least one frame, so empty ones don't restart forever
+			None if self.can_restart() && self.position.is_some() => {
+				self.restart()?;
+				self.next_frame()
+			}
+			// the video has ended, keep showing the last frame
+			None => {
+				self.position = Some(u64::MAX);
+				Ok(None)
+			}
+		}
+	}
+
+	fn interval(&self) -> Option<Duration> {
+		Some(self.interval)
+	}
+}
+
+/// Replaces the first `%d` or `%0Nd` placeholder in `pattern` with `number`.
+fn sequence_path(pattern: &str, number: u64) -> String {
+	let Some(start) = pattern.find('%') else {
+		return pattern.to_owned();
+	};
+	let Some(length) = pattern[start..].find('d') else {
+		return pattern.to_owned();
+	};
+	let width = pattern[start + 1..start + length].parse::<usize>().unwrap_or(0);
+	format!(
+		"{}{:0width$}{}",
+		&pattern[..start],
+		number,
+		&pattern[start + length + 1..],
+		width = width
+	)
+}
+
+fn open_y4m(path: &str) -> anyhow::Result<Y4mReader<Box<dyn BufRead + Send>>> {
+	let reader: Box<dyn BufRead + Send> = if path == "-" {
+		Box::new(BufReader::new(io::stdin()))
+	} else {
+		Box::new(BufReader::new(
+			File::open(path).with_context(|| format!("Failed to open video '{path}'"))?,
+		))
+	};
+	Y4mReader::new(reader)
+}
+
+/// Largest supported frame, enough for 8K with full resolution chroma.
+const MAX_FRAME_LENGTH: usize = 1 << 28;
+
+#[derive(Clone, Copy)]
+enum Chroma {
+	/// Horizontal and vertical subsampling factors
+	Subsampled(usize, usize),
+	Mono,
+}
+
+/// Minimal reader for 8 bit YUV4MPEG2 streams as produced by `ffmpeg -f yuv4mpegpipe`.
+struct Y4mReader<R> {
+	reader: R,
+	width: usize,
+	height: usize,
+	chroma: Chroma,
+	frame_rate: f64,
+	/// Bytes of one frame without its header
+	frame_length: usize,
+	buffer: Vec<u8>,
+}
+
+impl<R: BufRead> Y4mReader<R> {
+	fn new(mut reader: R) -> anyhow::Result<Self> {
+		let mut header = String::new();
+		reader.read_line(&mut header)?;
+		let mut parameters = header.split_whitespace();
+		if parameters.next() != Some("YUV4MPEG2") {
+			bail!("Not a YUV4MPEG2 stream.");
+		}
+
+		let (mut width, mut height, mut frame_rate, mut chroma) = (None, None, None, Chroma::Subsampled(2, 2));
+		for parameter in parameters {
+			let mut characters = parameter.chars();
+			let (Some(tag), value) = (characters.next(), characters.as_str()) else {
+				continue;
+			};
+			if !tag.is_ascii() {
+				bail!("Invalid Y4M header parameter '{parameter}'");
+			}
+			match tag {
+				'W' => width = Some(value.parse::<u32>().context("Invalid width in header")?),
+				'H' => height = Some(value.parse::<u32>().context("Invalid height in header")?),
+				'F' => {
+					let (numerator, denominator) = value.split_once(':').context("Invalid frame rate in header")?;
+					frame_rate = Some(numerator.parse::<f64>()? / denominator.parse::<f64>()?);
+				}
+				'C' => {
+					chroma = match value {
+						"420" | "420jpeg" | "420paldv" | "420mpeg2" => Chroma::Subsampled(2, 2),
+						"422" => Chroma::Subsampled(2, 1),
+						"444" => Chroma::Subsampled(1, 1),
+						"mono" => Chroma::Mono,
+						other => bail!("Unsupported Y4M color space '{other}', only 8 bit is supported."),
+					}
+				}
+				_ => {}
+			}
+		}
+
+		let width = width.context("Y4M header is missing the width")? as usize;
+		let height = height.context("Y4M header is missing the height")? as usize;
+		let mut reader = Self {
+			reader,
+			width,
+			height,
+			chroma,
+			frame_rate: frame_rate.unwrap_or(25.0),
+			frame_length: 0,
+			buffer: Vec::new(),
+		};
+		reader.frame_length = match reader.checked_frame_length() {
+			Some(length @ 1..=MAX_FRAME_LENGTH) => length,
+			_ => bail!("Unsupported Y4M frame size {width}x{height}"),
+		};
+		Ok(reader)
+	}
+
+	fn chroma_size(&self) -> (usize, usize) {
+		match self.chroma {
+			Chroma::Subsampled(horizontal, vertical) => {
+				(self.width.div_ceil(horizontal), self.height.div_ceil(vertical))
+			}
+			Chroma::Mono => (0, 0),
+		}
+	}
+
+	fn checked_frame_length(&self) -> Option<usize> {
+		let (chroma_width, chroma_height) = self.chroma_size();
+		let chroma_length = chroma_width.checked_mul(chroma_height)?.checked_mul(2)?;
+		self.width.checked_mul(self.height)?.checked_add(chroma_length)
+	}
+
+	/// Reads the next frame into the buffer, returns `false` at the end of the stream.
+	fn read_raw_frame(&mut self) -> anyhow::Result<bool> {
+		let mut frame_header = String::new();
+		if self.reader.read_line(&mut frame_header)? == 0 {
+			return Ok(false);
+		}
+		if !frame_header.starts_with("FRAME") {
+			bail!("Invalid Y4M frame header: {}", frame_header.trim_end());
+		}
+
+		self.buffer.resize(self.frame_length, 0);
+		match self.reader.read_exact(&mut self.buffer) {
+			Ok(()) => Ok(true),
+			// truncated last frame
+			Err(error) if error.kind() == ErrorKind::UnexpectedEof => Ok(false),
+			Err(error) => Err(anyhow!(error)),
+		}
+	}
+
+	fn read_frame(&mut self) -> anyhow::Result<Option<RgbImage>> {
+		if !self.read_raw_frame()? {
+			return Ok(None);
+		}
+
+		let (chroma_width, chroma_height) = self.chroma_size();
+		let (luma, chroma) = self.buffer.split_at(self.width * self.height);
+		let (u_plane, v_plane) = chroma.split_at(chroma_width * chroma_height);
+		let frame = RgbImage::from_fn(self.width as u32, self.height as u32, |x, y| {
+			let (x, y) = (x as usize, y as usize);
+			let luma = luma[y * self.width + x];
+			let (u, v) = match self.chroma {
+				Chroma::Subsampled(horizontal, vertical) => {
+					let index = (y / vertical) * chroma_width + x / horizontal;
+					(u_plane[index], v_plane[index])
+				}
+				Chroma::Mono => (128, 128),
+			};
+			yuv_to_rgb(luma, u, v)
+		});
+		Ok(Some(frame))
+	}
+}
+
+/// BT.601 limited range conversion
+fn yuv_to_rgb(y: u8, u: u8, v: u8) -> Rgb<u8> {
+	let y = 1.164 * (f32::from(y) - 16.0);
+	let u = f32::from(u) - 128.0;
+	let v = f32::from(v) - 128.0;
+	let clamp = |value: f32| value.round().clamp(0.0, 255.0) as u8;
+	Rgb([
+		clamp(y + 1.596 * v),
+		clamp(y - 0.392 * u - 0.813 * v),
+		clamp(y + 2.017 * u),
+	])
+}
@@ -0,0 +1,223 @@
+use crate::frame_source::FrameSource;
+use crate::settings;
+use crate::settings::TextStyle;
+use crate::text::{load_font, render_text};
+use ab_glyph::FontVec;
+use anyhow::{bail, Context};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{Local, NaiveDateTime, TimeZone, Timelike};
+use image::{DynamicImage, Rgba, RgbaImage};
+use std::f64::consts::TAU;
+use std::fs;
+use std::time::{Duration, SystemTime};
+
+const CLOCK_INTERVAL: Duration = Duration::from_millis(200);
+const FILE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Renders text that is regenerated periodically, new frames are only produced if the text changed.
+pub struct TextWidget {
+	font: FontVec,
+	style: TextStyle,
+	content: Box<dyn FnMut() -> anyhow::Result<String> + Send>,
+	interval: Duration,
+	last_text: Option<String>,
+}
+
+impl TextWidget {
+	fn new(
+		style: TextStyle,
+		interval: Duration,
+		content: impl FnMut() -> anyhow::Result<String> + Send + 'static,
+	) -> anyhow::Result<Self> {
+		Ok(Self {
+			font: load_font(&style.font)?,
+			style,
+			content: Box::new(content),
+			interval,
+			last_text: None,
+		})
+	}
+
+	pub fn digital_clock(settings: settings::DigitalClock) -> anyhow::Result<Self> {
+		let format = settings.format;
+		if StrftimeItems::new(&format).any(|item| item == Item::Error) {
+			bail!("Invalid clock format '{format}'");
+		}
+		Self::new(settings.style, CLOCK_INTERVAL, move || {
+			Ok(Local::now().format(&format).to_string())
+		})
+	}
+
+	pub fn countdown(settings: settings::Countdown) -> anyhow::Result<Self> {
+		let target = NaiveDateTime::parse_from_str(&settings.target, "%Y-%m-%d %H:%M:%S")
+			.context("Invalid countdown target, expected 'YYYY-MM-DD HH:MM:SS'")?;
+		let target = Local
+			.from_local_datetime(&target)
+			.earliest()
+			.context("Countdown target doesn't exist in the local time zone")?;
+		let finished_text = settings.finished_text;
+		Self::new(settings.style, CLOCK_INTERVAL, move || {
+			let remaining = (target - Local::now()).num_seconds();
+			if remaining <= 0 {
+				return Ok(finished_text.clone());
+			}
+
+			let (days, hours, minutes, seconds) = (
+				remaining / 86400,
+				remaining / 3600 % 24,
+				remaining / 60 % 60,
+				remaining % 60,
+			);
+			Ok(match days {
+				0 => format!("{hours:02}:{minutes:02}:{seconds:02}"),
+				days => format!("{days}d {hours:02}:{minutes:02}:{seconds:02}"),
+			})
+		})
+	}
+
+	/// Shows the contents of a text file, the file is only read again after it was modified.
+	pub fn text_file(settings: settings::TextFile) -> anyhow::Result<Self> {
+		let path = settings.path;
+		let mut last_modified = None::<SystemTime>;
+		let mut contents = String::new();
+		Self::new(settings.style, FILE_POLL_INTERVAL, move || {
+			let update = fs::metadata(&path)
+				.and_then(|metadata| metadata.modified())
+				.and_then(|modified| match last_modified == Some(modified) {
+					true => Ok(None),
+					false => fs::read_to_string(&path).map(|contents| Some((modified, contents))),
+				});
+			match update {
+				Ok(Some((modified, new_contents))) => {
+					contents = new_contents;
+					last_modified = Some(modified);
+				}
+				Ok(None) => {}
+				// Editors saving via rename make the file disappear briefly, keep showing the last contents
+				Err(error) if last_modified.is_some() => {
+					println!("Failed to read '{path}', keeping the last contents: {error}")
+				}
+				Err(error) => return Err(error).with_context(|| format!("Failed to read '{path}'")),
+			}
+			Ok(contents.clone())
+		})
+	}
+}
+
+impl FrameSource for TextWidget {
+	fn next_frame(&mut self) -> anyhow::Result<Option<DynamicImage>> {
+		let text = (self.content)()?;
+		if self.last_text.as_ref() == Some(&text) {
+			return Ok(None);
+		}
+
+		let frame = render_text(&self.font, &text, &self.style);
+		self.last_text = Some(text);
+		Ok(Some(DynamicImage::ImageRgba8(frame)))
+	}
+
+	fn interval(&self) -> Option<Duration> {
+		Some(self.interval)
+	}
+}
+
+/// Clock face with hour, minute and second hands, redrawn every second.
+pub struct AnalogClock {
+	size: u32,
+	settings: settings::AnalogClock,
+	last_second: Option<u32>,
+}
+
+impl AnalogClock {
+	pub fn new(settings: settings::AnalogClock, size: u32) -> Self {
+		Self {
+			size,
+			settings,
+			last_second: None,
+		}
+	}
+
+	fn draw(&self, hour: u32, minute: u32, second: u32) -> RgbaImage {
+		let size = self.size as f64;
+		let center = size / 2.0;
+		let radius = center - 1.0;
+		let background = self.settings.background.map(Rgba::from).unwrap_or(Rgba([0, 0, 0, 0]));
+
+		let minutes = f64::from(minute) + f64::from(second) / 60.0;
+		let hours = f64::from(hour % 12) + minutes / 60.0;
+		// (fraction of a full turn, length and width relative to the radius, color)
+		let hands = [
+			(hours / 12.0, 0.5, 0.06, self.settings.foreground),
+			(minutes / 60.0, 0.8, 0.04, self.settings.foreground),
+			(f64::from(second) / 60.0, 0.9, 0.015, self.settings.second_hand),
+		];
+		let hand_ends = hands.map(|(turn, length, width, color)| {
+			let angle = turn * TAU;
+			let end = (
+				center + angle.sin() * radius * length,
+				center - angle.cos() * radius * length,
+			);
+			(end, width * radius, color)
+		});
+
+		RgbaImage::from_fn(self.size, self.size, |x, y| {
+			let point = (f64::from(x) + 0.5, f64::from(y) + 0.5);
+			let distance = ((point.0 - center).powi(2) + (point.1 - center).powi(2)).sqrt();
+			if distance > radius {
+				return Rgba([0, 0, 0, 0]);
+			}
+
+			// draw the second hand last, so it is on top
+			for (end, width, color) in hand_ends.iter().rev() {
+				if distance_to_segment(point, (center, center), *end) <= *width {
+					return Rgba::from(*color);
+				}
+			}
+
+			let rim_width = (radius * 0.04).max(1.0);
+			if distance > radius - rim_width {
+				return Rgba::from(self.settings.foreground);
+			}
+
+			// hour ticks
+			let angle = (point.0 - center).atan2(center - point.1).rem_euclid(TAU);
+			let tick = (angle / TAU * 12.0).round() / 12.0 * TAU;
+			let tick_distance = (angle - tick).abs() * distance;
+			if distance > radius * 0.85 && tick_distance < rim_width {
+				return Rgba::from(self.settings.foreground);
+			}
+
+			background
+		})
+	}
+}
+
+impl FrameSource for AnalogClock {
+	fn next_frame(&mut self) -> anyhow::Result<Option<DynamicImage>> {
+		let now = Local::now();
+		if self.last_second == Some(now.second()) {
+			return Ok(None);
+		}
+
+		self.last_second = Some(now.second());
+		let frame = self.draw(now.hour(), now.minute(), now.second());
+		Ok(Some(DynamicImage::ImageRgba8(frame)))
+	}
+
+	fn interval(&self) -> Option<Duration> {
+		Some(CLOCK_INTERVAL)
+	}
+}
+
+fn distance_to_segment(point: (f64, f64), start: (f64, f64), end: (f64, f64)) -> f64 {
+	let segment = (end.0 - start.0, end.1 - start.1);
+	let to_point = (point.0 - start.0, point.1 - start.1);
+	let length_squared = segment.0 * segment.0 + segment.1 * segment.1;
+	let projection = if length_squared == 0.0 {
+		0.0
+	} else {
+		((to_point.0 * segment.0 + to_point.1 * segment.1) / length_squared).clamp(0.0, 1.0)
+	};
+	let closest = (start.0 + projection * segment.0, start.1 + projection * segment.1);
+	((point.0 - closest.0).powi(2) + (point.1 - closest.1).powi(2)).sqrt()
+}